@@ -1,5 +1,6 @@
-use suitcase::run_from_alias;
-
-fn main() -> anyhow::Result<()> {
-    run_from_alias(None)
-}
+use std::process::ExitCode;
+use suitcase::{exit_code, run_from_alias};
+
+fn main() -> ExitCode {
+    exit_code(run_from_alias(None))
+}
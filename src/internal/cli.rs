@@ -1,63 +1,96 @@
-use crate::internal;
-use clap::{Args, Parser, Subcommand};
-
-/// A set of personal CLI tools to automate common tasks in software development
-/// (including Rust, Dart, and Flutter), written in Rust.
-#[derive(Parser)]
-#[clap(author, version, about, long_about = None)]
-#[clap(propagate_version = true)]
-pub struct Cli {
-    #[clap(subcommand)]
-    /// The command to run.
-    pub command: Command,
-
-    /// Base arguments that are shared across all subcommands.
-    #[clap(flatten)]
-    pub base_args: BaseArgs,
-}
-
-/// A single suitcase subcommand to run.
-#[derive(Subcommand)]
-pub enum Command {
-    /// Open the current directory's Git repository in the default browser.
-    ///
-    /// Optionally provide a path to open a specific directory's repository
-    /// other than the current directory.
-    ///
-    /// Command name is short for "github open"
-    #[clap(name = "gho")]
-    GitHubOpen(internal::commands::gho::GitHubOpenOptions),
-
-    /// Upgrade suitcase to the latest version from cargo or from a local path.
-    ///
-    /// This command will download and install the latest version of suitcase
-    /// from crates.io, or from a local path if it was installed from source.
-    #[clap(name = "upgrade")]
-    Upgrade(internal::commands::upgrade::UpgradeOptions),
-
-    /// Run the given command for every Dart project in the current directory
-    /// and its subdirectories recursively.
-    ///
-    /// Command name is short for "for dart"
-    #[clap(name = "ford")]
-    ForEveryDartProject(internal::commands::ford::ForEveryDartProjectOptions),
-
-    /// Set the FVM version for every Flutter or Dart project in the current directory
-    /// and its subdirectories recursively.
-    ///
-    /// Command name is short for "flutter use all"
-    #[clap(name = "fua")]
-    FvmUseForEveryFlutterProject(internal::commands::fua::FvmUseForEveryFlutterProjectOptions),
-}
-
-/// Base arguments that are shared across all subcommands.
-#[derive(Clone, Args)]
-pub struct BaseArgs {
-    /// Print verbose output. Cannot be used in combination with `--quiet`.
-    #[clap(short, long, global = true)]
-    pub verbose: bool,
-
-    /// Print no output. Cannot be used in combination with `--verbose`.
-    #[clap(short, long, global = true)]
-    pub quiet: bool,
-}
+use crate::internal::{self, report::OutputFormat};
+use clap::{Args, Parser, Subcommand};
+
+/// A set of personal CLI tools to automate common tasks in software development
+/// (including Rust, Dart, and Flutter), written in Rust.
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(propagate_version = true)]
+pub struct Cli {
+    #[clap(subcommand)]
+    /// The command to run.
+    pub command: Command,
+
+    /// Base arguments that are shared across all subcommands.
+    #[clap(flatten)]
+    pub base_args: BaseArgs,
+}
+
+/// A single suitcase subcommand to run.
+#[derive(Subcommand)]
+pub enum Command {
+    /// Open the current directory's Git repository in the default browser.
+    ///
+    /// Optionally provide a path to open a specific directory's repository
+    /// other than the current directory.
+    ///
+    /// Command name is short for "github open"
+    #[clap(name = "gho")]
+    GitHubOpen(internal::commands::gho::GitHubOpenOptions),
+
+    /// Upgrade suitcase to the latest version from cargo or from a local path.
+    ///
+    /// This command will download and install the latest version of suitcase
+    /// from crates.io, or from a local path if it was installed from source.
+    #[clap(name = "upgrade")]
+    Upgrade(internal::commands::upgrade::UpgradeOptions),
+
+    /// Run the given command for every Dart project in the current directory
+    /// and its subdirectories recursively.
+    ///
+    /// Command name is short for "for dart"
+    #[clap(name = "ford")]
+    ForEveryDartProject(internal::commands::ford::ForEveryDartProjectOptions),
+
+    /// Set the FVM version for every Flutter or Dart project in the current directory
+    /// and its subdirectories recursively.
+    ///
+    /// Command name is short for "flutter use all"
+    #[clap(name = "fua")]
+    FvmUseForEveryFlutterProject(internal::commands::fua::FvmUseForEveryFlutterProjectOptions),
+
+    /// Inspect the dependencies between the Dart projects in the current directory
+    /// and its subdirectories recursively.
+    ///
+    /// Command name is short for "dependencies"
+    #[clap(name = "deps")]
+    Dependencies(internal::commands::deps::DependenciesOptions),
+
+    /// Inspect the Flutter versions used by the Dart projects in the current directory
+    /// and its subdirectories recursively.
+    #[clap(name = "fvm")]
+    Fvm(internal::commands::fvm::FvmOptions),
+
+    /// Inspect the configuration that is loaded from `suitcase.yaml` files.
+    ///
+    /// The repository configuration is found by walking up from the current directory,
+    /// the user configuration is read from `$XDG_CONFIG_HOME/suitcase/suitcase.yaml`.
+    #[clap(name = "config")]
+    Config(internal::commands::config::ConfigOptions),
+}
+
+/// Base arguments that are shared across all subcommands.
+#[derive(Clone, Args)]
+pub struct BaseArgs {
+    /// Print verbose output. Cannot be used in combination with `--quiet`.
+    #[clap(short, long, global = true)]
+    pub verbose: bool,
+
+    /// Print no output. Cannot be used in combination with `--verbose`.
+    #[clap(short, long, global = true)]
+    pub quiet: bool,
+
+    /// The format in which to report results on stdout (for use in scripts and CI).
+    #[clap(long, global = true, value_enum, default_value = "text")]
+    pub output: OutputFormat,
+
+    /// Print the commands that would change something (with their working directory) instead of
+    /// running them. Read-only steps, like finding projects, still run.
+    #[clap(long, global = true)]
+    pub dry_run: bool,
+
+    /// Remove ANSI escape sequences (like colors and progress bars) from the output of commands
+    /// before logging it, which keeps logs readable when they are written to a file.
+    #[clap(long, global = true)]
+    pub strip_ansi: bool,
+}
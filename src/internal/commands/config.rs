@@ -0,0 +1,117 @@
+use crate::internal::{
+    config::{Config, Sourced},
+    report::{OutputFormat, Record, Reporter},
+};
+use clap::{Args, Subcommand};
+
+use super::InternalCommandOptions;
+
+#[derive(Args, Debug)]
+pub struct ConfigOptions {
+    #[command(subcommand)]
+    command: ConfigCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Print the effective configuration (merged from all configuration files) and where each
+    /// value came from.
+    Show(ConfigShowOptions),
+}
+
+#[derive(Args, Debug)]
+pub struct ConfigShowOptions {}
+
+pub fn config(
+    InternalCommandOptions {
+        shell,
+        base_args,
+        config,
+        options,
+    }: InternalCommandOptions<ConfigOptions>,
+) -> anyhow::Result<()> {
+    match &options.command {
+        ConfigCommand::Show(options) => config_show(InternalCommandOptions {
+            shell,
+            base_args,
+            config,
+            options,
+        }),
+    }
+}
+
+pub fn config_show(
+    InternalCommandOptions {
+        shell: _,
+        base_args,
+        config,
+        options: _,
+    }: InternalCommandOptions<ConfigShowOptions>,
+) -> anyhow::Result<()> {
+    if base_args.output != OutputFormat::Text {
+        Reporter::new(base_args.output).report(Record::Config(config.clone()));
+        return Ok(());
+    }
+
+    let describe_path = |path: &Option<std::path::PathBuf>| match path {
+        Some(path) => path.display().to_string(),
+        None => "(not found)".to_string(),
+    };
+
+    println!("config files:");
+    println!(
+        "  user: {}",
+        match (&config.user_config_path, Config::user_config_path()) {
+            (Some(path), _) => path.display().to_string(),
+            (None, Some(path)) => format!("{} (not found)", path.display()),
+            (None, None) => "(not found)".to_string(),
+        }
+    );
+    println!(
+        "  repository: {}",
+        describe_path(&config.repository_config_path)
+    );
+
+    println!("ignored_folders:");
+    print_list(2, &config.ignored_folders);
+
+    println!("defaults:");
+    print_value("fail_fast", &config.fail_fast);
+    print_value("show_output", &config.show_output);
+    print_value("jobs", &config.jobs);
+
+    println!("projects:");
+    println!("  include:");
+    print_list(4, &config.include);
+    println!("  exclude:");
+    print_list(4, &config.exclude);
+
+    println!("aliases:");
+    if config.aliases.is_empty() {
+        println!("  (none)");
+    }
+    for (name, command) in &config.aliases {
+        print_value(name, command);
+    }
+
+    match &config.browser {
+        Some(browser) => println!("browser: {} ({})", browser.value, browser.source),
+        None => println!("browser: (auto-detected)"),
+    }
+
+    Ok(())
+}
+
+fn print_value<T: std::fmt::Display>(name: &str, value: &Sourced<T>) {
+    println!("  {}: {} ({})", name, value.value, value.source);
+}
+
+fn print_list(indent: usize, values: &[Sourced<String>]) {
+    let indent = " ".repeat(indent);
+    if values.is_empty() {
+        println!("{}(none)", indent);
+    }
+    for value in values {
+        println!("{}- {} ({})", indent, value.value, value.source);
+    }
+}
@@ -0,0 +1,548 @@
+use crate::internal::{
+    report::{OutputFormat, Record, Reporter},
+    utils::{
+        dependency_graph::DependencyGraph,
+        pubspec::{Dependency, DependencySource},
+    },
+};
+use anyhow::Context;
+use clap::{Args, Subcommand, ValueEnum};
+use log::info;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use super::{
+    workspace_runner::{ProjectDiscoveryOptions, ProjectKind},
+    InternalCommandOptions,
+};
+
+#[derive(Args, Debug)]
+pub struct DependenciesOptions {
+    #[command(subcommand)]
+    command: DependenciesCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DependenciesCommand {
+    /// Export the dependency graph between the Dart projects in the given path as DOT, Mermaid
+    /// or JSON.
+    Graph(DependencyGraphOptions),
+}
+
+#[derive(Args, Debug)]
+pub struct DependencyGraphOptions {
+    /// Options for finding the projects to include in the graph.
+    #[command(flatten)]
+    discovery: ProjectDiscoveryOptions,
+
+    /// The format in which to export the graph. With `--output json` or `--output ndjson`, the
+    /// graph is printed as a JSON record instead (and this format only applies to `--output-file`).
+    #[arg(value_enum, default_value = "dot", short, long)]
+    format: GraphFormat,
+
+    /// Include external (hosted) dependencies of the local projects in the graph.
+    #[arg(default_value = "false", short, long)]
+    include_external: bool,
+
+    /// Highlight dependency cycles between local projects in the graph.
+    #[arg(default_value = "false", short = 'c', long)]
+    highlight_cycles: bool,
+
+    /// Write the graph to the given file instead of printing it.
+    #[arg(long)]
+    output_file: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum GraphFormat {
+    /// Graphviz DOT.
+    Dot,
+    /// A Mermaid flowchart.
+    Mermaid,
+    /// A JSON document with all nodes, edges and cycles.
+    Json,
+}
+
+pub fn dependencies(
+    InternalCommandOptions {
+        shell,
+        base_args,
+        config,
+        options,
+    }: InternalCommandOptions<DependenciesOptions>,
+) -> anyhow::Result<()> {
+    match &options.command {
+        DependenciesCommand::Graph(options) => dependency_graph(InternalCommandOptions {
+            shell,
+            base_args,
+            config,
+            options,
+        }),
+    }
+}
+
+pub fn dependency_graph(
+    InternalCommandOptions {
+        shell,
+        base_args,
+        config,
+        options,
+    }: InternalCommandOptions<DependencyGraphOptions>,
+) -> anyhow::Result<()> {
+    let path = &options.discovery.path;
+    let projects = options
+        .discovery
+        .find_projects(shell, config, ProjectKind::All)?;
+
+    let root = path
+        .canonicalize()
+        .context(format!("trying to resolve path '{}'", path.display()))?;
+    let graph = DependencyGraph::new(&projects);
+    let export = GraphExport::new(&graph, &root, options.include_external);
+
+    if !export.cycles.is_empty() {
+        info!(
+            "Found {} dependency cycle(s) between local projects",
+            export.cycles.len()
+        );
+    }
+
+    let render = || -> anyhow::Result<String> {
+        Ok(match options.format {
+            GraphFormat::Dot => export.to_dot(options.highlight_cycles),
+            GraphFormat::Mermaid => export.to_mermaid(options.highlight_cycles),
+            GraphFormat::Json => serde_json::to_string_pretty(&export)
+                .context("trying to serialize graph to JSON")?,
+        })
+    };
+
+    match &options.output_file {
+        Some(output_file) => {
+            std::fs::write(output_file, format!("{}\n", render()?)).context(format!(
+                "trying to write graph to file '{}'",
+                output_file.display()
+            ))?;
+            info!("Wrote dependency graph to '{}'", output_file.display());
+        }
+        None if base_args.output == OutputFormat::Text => println!("{}", render()?),
+        None => {}
+    }
+
+    // Machine-readable output always contains the graph itself, regardless of `--format`.
+    if base_args.output != OutputFormat::Text {
+        Reporter::new(base_args.output).report(Record::DependencyGraph(export));
+    }
+
+    Ok(())
+}
+
+/// A format-independent representation of a dependency graph that is ready to be exported.
+///
+/// Local projects are identified by their path relative to the search path (since multiple
+/// projects can have the same name, like `example`), external packages by `external:<NAME>`.
+#[derive(Serialize, Debug)]
+pub struct GraphExport {
+    nodes: Vec<GraphNode>,
+    edges: Vec<GraphEdge>,
+    cycles: Vec<Vec<String>>,
+}
+
+#[derive(Serialize, Debug)]
+struct GraphNode {
+    id: String,
+    name: String,
+    path: Option<PathBuf>,
+    version: Option<String>,
+    external: bool,
+    in_cycle: bool,
+}
+
+#[derive(Serialize, Debug)]
+struct GraphEdge {
+    from: String,
+    to: String,
+    constraint: Option<String>,
+    in_cycle: bool,
+}
+
+impl GraphExport {
+    fn new(graph: &DependencyGraph, root: &Path, include_external: bool) -> Self {
+        let projects = graph.projects();
+        let ids: Vec<String> = projects
+            .iter()
+            .map(|project| {
+                let relative_path = project.path.strip_prefix(root).unwrap_or(&project.path);
+                if relative_path.as_os_str().is_empty() {
+                    ".".to_string()
+                } else {
+                    relative_path.to_string_lossy().replace('\\', "/")
+                }
+            })
+            .collect();
+        let cycles = graph.cycles();
+        let cycle_of: BTreeMap<usize, usize> = cycles
+            .iter()
+            .enumerate()
+            .flat_map(|(cycle_index, cycle)| cycle.iter().map(move |index| (*index, cycle_index)))
+            .collect();
+
+        let mut nodes: Vec<GraphNode> = projects
+            .iter()
+            .enumerate()
+            .map(|(index, project)| GraphNode {
+                id: ids[index].clone(),
+                name: project.name.clone(),
+                path: Some(project.path.clone()),
+                version: project.pubspec.version.clone(),
+                external: false,
+                in_cycle: cycle_of.contains_key(&index),
+            })
+            .collect();
+
+        let mut edges = vec![];
+        for (index, id) in ids.iter().enumerate() {
+            for dependency in graph.dependencies_of(index) {
+                edges.push(GraphEdge {
+                    from: id.clone(),
+                    to: ids[*dependency].clone(),
+                    constraint: None,
+                    in_cycle: cycle_of.contains_key(&index)
+                        && cycle_of.get(&index) == cycle_of.get(dependency),
+                });
+            }
+        }
+
+        if include_external {
+            let mut external_names = BTreeSet::new();
+            let mut external_edges = BTreeSet::new();
+            for (index, project) in projects.iter().enumerate() {
+                let hosted_dependencies = project
+                    .pubspec
+                    .dependencies
+                    .iter()
+                    .chain(project.pubspec.dev_dependencies.iter())
+                    .filter(|(_, dependency)| is_hosted(dependency));
+
+                for (name, dependency) in hosted_dependencies {
+                    let id = format!("external:{}", name);
+                    if external_names.insert(name.clone()) {
+                        nodes.push(GraphNode {
+                            id: id.clone(),
+                            name: name.clone(),
+                            path: None,
+                            version: None,
+                            external: true,
+                            in_cycle: false,
+                        });
+                    }
+
+                    // Packages can be both a regular and a dev dependency, but only the first
+                    // (regular) one is exported.
+                    if external_edges.insert((index, name)) {
+                        edges.push(GraphEdge {
+                            from: ids[index].clone(),
+                            to: id,
+                            constraint: dependency.version.clone(),
+                            in_cycle: false,
+                        });
+                    }
+                }
+            }
+        }
+
+        Self {
+            nodes,
+            edges,
+            cycles: cycles
+                .into_iter()
+                .map(|cycle| cycle.into_iter().map(|index| ids[index].clone()).collect())
+                .collect(),
+        }
+    }
+
+    /// Renders the graph in the Graphviz DOT format.
+    fn to_dot(&self, highlight_cycles: bool) -> String {
+        let mut lines = vec![
+            "digraph dependencies {".to_string(),
+            "  rankdir=LR;".to_string(),
+            "  node [shape=box];".to_string(),
+        ];
+
+        for node in &self.nodes {
+            let mut attributes = vec![format!("label={}", dot_string(&node.name))];
+            if node.external {
+                attributes.push("style=dashed".to_string());
+            }
+            if highlight_cycles && node.in_cycle {
+                attributes.push("color=red".to_string());
+            }
+            lines.push(format!(
+                "  {} [{}];",
+                dot_string(&node.id),
+                attributes.join(", ")
+            ));
+        }
+
+        for edge in &self.edges {
+            let attributes = if highlight_cycles && edge.in_cycle {
+                " [color=red]"
+            } else {
+                ""
+            };
+            lines.push(format!(
+                "  {} -> {}{};",
+                dot_string(&edge.from),
+                dot_string(&edge.to),
+                attributes
+            ));
+        }
+
+        lines.push("}".to_string());
+        lines.join("\n")
+    }
+
+    /// Renders the graph as a Mermaid flowchart, where nodes are numbered in order (since node ids
+    /// may contain characters that Mermaid does not allow in ids).
+    fn to_mermaid(&self, highlight_cycles: bool) -> String {
+        let mermaid_ids: BTreeMap<&str, String> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| (node.id.as_str(), format!("n{}", index)))
+            .collect();
+        let mut lines = vec!["flowchart LR".to_string()];
+
+        for node in &self.nodes {
+            let id = &mermaid_ids[node.id.as_str()];
+            if node.external {
+                lines.push(format!("  {}([\"{}\"])", id, node.name));
+            } else {
+                lines.push(format!("  {}[\"{}\"]", id, node.name));
+            }
+        }
+
+        let mut cycle_edge_indices = vec![];
+        for (index, edge) in self.edges.iter().enumerate() {
+            let arrow = if edge.to.starts_with("external:") {
+                "-.->"
+            } else {
+                "-->"
+            };
+            lines.push(format!(
+                "  {} {} {}",
+                mermaid_ids[edge.from.as_str()],
+                arrow,
+                mermaid_ids[edge.to.as_str()]
+            ));
+            if edge.in_cycle {
+                cycle_edge_indices.push(index.to_string());
+            }
+        }
+
+        if highlight_cycles && !self.cycles.is_empty() {
+            let cycle_node_ids = self
+                .nodes
+                .iter()
+                .filter(|node| node.in_cycle)
+                .map(|node| mermaid_ids[node.id.as_str()].as_str())
+                .collect::<Vec<_>>();
+
+            lines.push("  classDef cycle stroke:#f00,stroke-width:2px".to_string());
+            lines.push(format!("  class {} cycle", cycle_node_ids.join(",")));
+            lines.push(format!(
+                "  linkStyle {} stroke:#f00",
+                cycle_edge_indices.join(",")
+            ));
+        }
+
+        lines.join("\n")
+    }
+}
+
+/// Quotes the given text as a DOT string, escaping quotes and backslashes.
+fn dot_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn is_hosted(dependency: &Dependency) -> bool {
+    matches!(dependency.source, DependencySource::Hosted { .. })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::{
+        commands::test_utils::{base_args, create_project_with_pubspec},
+        config::Config,
+        shell::ScriptedShell,
+        utils::{dart::DartShell, project_filter::ProjectFilterOptions},
+    };
+
+    fn export(root: &Path, include_external: bool) -> GraphExport {
+        let root = root.canonicalize().unwrap();
+        let projects = DartShell::new(Config::default().ignored_folders())
+            .find_dart_projects(Some(&root), None)
+            .unwrap();
+        GraphExport::new(&DependencyGraph::new(&projects), &root, include_external)
+    }
+
+    /// Creates two projects that depend on each other, where `a` also depends on `http`.
+    fn create_cycle(root: &Path) {
+        create_project_with_pubspec(
+            root,
+            "a",
+            "name: a\ndependencies:\n  b:\n    path: ../b\n  http: ^1.0.0\n",
+        );
+        create_project_with_pubspec(root, "b", "name: b\ndependencies:\n  a:\n    path: ../a\n");
+    }
+
+    #[test]
+    fn exports_projects_with_the_same_name_as_separate_dot_nodes() {
+        let root = tempfile::tempdir().unwrap();
+        create_project_with_pubspec(
+            root.path(),
+            "app",
+            "name: app\ndependencies:\n  ui:\n    path: ../packages/ui\n",
+        );
+        create_project_with_pubspec(root.path(), "packages/ui", "name: ui\n");
+        for folder in ["app/example", "packages/ui/example"] {
+            let parent = folder.trim_end_matches("/example");
+            let parent_name = parent.rsplit('/').next().unwrap();
+            create_project_with_pubspec(
+                root.path(),
+                folder,
+                &format!(
+                    "name: example\ndependencies:\n  {}:\n    path: ..\n",
+                    parent_name
+                ),
+            );
+        }
+
+        assert_eq!(
+            export(root.path(), false).to_dot(false),
+            [
+                "digraph dependencies {",
+                "  rankdir=LR;",
+                "  node [shape=box];",
+                "  \"app\" [label=\"app\"];",
+                "  \"app/example\" [label=\"example\"];",
+                "  \"packages/ui\" [label=\"ui\"];",
+                "  \"packages/ui/example\" [label=\"example\"];",
+                "  \"app\" -> \"packages/ui\";",
+                "  \"app/example\" -> \"app\";",
+                "  \"packages/ui/example\" -> \"packages/ui\";",
+                "}",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn escapes_dot_ids_and_exports_every_external_edge_once() {
+        let root = tempfile::tempdir().unwrap();
+        create_project_with_pubspec(
+            root.path(),
+            r#"say "hi"/app"#,
+            "name: app\ndependencies:\n  http: ^1.0.0\ndev_dependencies:\n  http: ^1.1.0\n",
+        );
+
+        assert_eq!(
+            export(root.path(), true).to_dot(false),
+            [
+                "digraph dependencies {",
+                "  rankdir=LR;",
+                "  node [shape=box];",
+                r#"  "say \"hi\"/app" [label="app"];"#,
+                r#"  "external:http" [label="http", style=dashed];"#,
+                r#"  "say \"hi\"/app" -> "external:http";"#,
+                "}",
+            ]
+            .join("\n")
+        );
+        assert_eq!(dot_string(r#"C:\"app""#), r#""C:\\\"app\"""#);
+    }
+
+    #[test]
+    fn exports_mermaid_with_external_packages_and_highlighted_cycles() {
+        let root = tempfile::tempdir().unwrap();
+        create_cycle(root.path());
+
+        assert_eq!(
+            export(root.path(), true).to_mermaid(true),
+            [
+                "flowchart LR",
+                "  n0[\"a\"]",
+                "  n1[\"b\"]",
+                "  n2([\"http\"])",
+                "  n0 --> n1",
+                "  n1 --> n0",
+                "  n0 -.-> n2",
+                "  classDef cycle stroke:#f00,stroke-width:2px",
+                "  class n0,n1 cycle",
+                "  linkStyle 0,1 stroke:#f00",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn exports_json_with_nodes_edges_and_cycles() {
+        let root = tempfile::tempdir().unwrap();
+        create_cycle(root.path());
+
+        let json = serde_json::to_value(export(root.path(), true)).unwrap();
+        let ids: Vec<&str> = json["nodes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|node| node["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, ["a", "b", "external:http"]);
+        assert_eq!(json["nodes"][2]["external"], true);
+        assert_eq!(
+            json["edges"][2],
+            serde_json::json!({
+                "from": "a",
+                "to": "external:http",
+                "constraint": "^1.0.0",
+                "in_cycle": false,
+            })
+        );
+        assert_eq!(json["cycles"], serde_json::json!([["a", "b"]]));
+    }
+
+    #[test]
+    fn only_includes_projects_matching_the_filters() {
+        let root = tempfile::tempdir().unwrap();
+        create_cycle(root.path());
+        let output_file = root.path().join("graph.dot");
+
+        dependency_graph(InternalCommandOptions {
+            shell: &ScriptedShell::new(),
+            base_args: &base_args(),
+            config: &Config::default(),
+            options: &DependencyGraphOptions {
+                discovery: ProjectDiscoveryOptions {
+                    path: root.path().to_path_buf(),
+                    filter: ProjectFilterOptions {
+                        include: vec!["a".to_string()],
+                        ..ProjectFilterOptions::default()
+                    },
+                    ..ProjectDiscoveryOptions::default()
+                },
+                format: GraphFormat::Dot,
+                include_external: false,
+                highlight_cycles: false,
+                output_file: Some(output_file.clone()),
+            },
+        })
+        .unwrap();
+
+        let graph = std::fs::read_to_string(output_file).unwrap();
+        assert!(graph.contains("\"a\" [label=\"a\"];"));
+        assert!(!graph.contains("\"b\""));
+    }
+}
@@ -1,158 +1,361 @@
-use crate::{
-    exec_on,
-    internal::{
-        shell::ShellError,
-        utils::{
-            dart::{DartProjectMetadata, DartShell},
-            directory_utils::DirectoryUtils,
-        },
-    },
-    progress,
-};
-use anyhow::Context;
-use clap::Args;
-use log::info;
-use std::{ops::Deref, path::PathBuf};
-use thiserror::Error;
-
-use super::InternalCommandOptions;
-
-#[derive(Args, Debug)]
-pub struct ForEveryDartProjectOptions {
-    /// The command to run on each Dart project.
-    #[arg()]
-    command: Vec<String>,
-
-    /// The path from which to search for Dart projects.
-    #[arg(default_value = ".", short, long)]
-    path: PathBuf,
-
-    /// Include Flutter projects when searching for Dart projects to run the command on.
-    #[arg(default_value = "true", short, long)]
-    include_flutter_projects: bool,
-
-    /// Exit the process immediately if any of the commands run on the Dart projects fail.
-    #[arg(default_value = "false", short, long)]
-    fail_fast: bool,
-
-    /// Show the output of the command run on each Dart project.
-    #[arg(default_value = "false", short, long)]
-    show_output: bool,
-}
-
-#[derive(Error, Debug)]
-pub enum ForEveryDartProjectError {
-    /// An error that occurred when trying to execute a command in one or more Dart projects.
-    #[error("error while executing command '{command}' for one or more projects: {errors:?}")]
-    CommandExecutionError {
-        command: String,
-        errors: Vec<(String, ShellError)>,
-    },
-}
-
-pub fn for_every_dart_project(
-    InternalCommandOptions {
-        shell,
-        base_args: _,
-        options,
-    }: InternalCommandOptions<ForEveryDartProjectOptions>,
-) -> anyhow::Result<()> {
-    let command = options.command.join(" ");
-    let path = options.path.clone();
-    let mut dir_utils = DirectoryUtils::new();
-
-    dir_utils
-        .pushd(&path)
-        .context("trying to navigate to provided path")?;
-
-    let dart_shell = DartShell::new(shell);
-
-    let mut projects = progress!("Finding Dart projects", {
-        dart_shell.find_dart_projects(None).context(format!(
-            "trying to find Dart projects in path '{}'",
-            path.display()
-        ))
-    })?;
-
-    if !options.include_flutter_projects {
-        projects.retain(|project| !project.is_flutter_project);
-        info!("Found {} Dart (non-Flutter) projects", projects.len());
-    } else {
-        info!("Found {} Dart and Flutter projects", projects.len());
-    }
-
-    if projects.is_empty() {
-        info!("No projects found");
-        return Ok(());
-    }
-
-    let mut errors: Vec<(&DartProjectMetadata, ShellError)> = vec![];
-
-    for project in projects.iter() {
-        dir_utils.pushd(&project.path).context(format!(
-            "trying to navigate to project path '{}'",
-            project.path.display()
-        ))?;
-
-        let result = progress!(
-            format!(
-                "Running command in '{}' ('{}')",
-                project.name,
-                project.path.display()
-            )
-            .as_str(),
-            exec_on!(shell, "bash", "-c", &command)
-        );
-
-        if let Err(error) = &result {
-            let error = error.deref().clone();
-            if options.fail_fast {
-                return Err(error).context(format!(
-                    "trying to run command '{}' on project '{}'",
-                    command, project.name
-                ));
-            } else {
-                errors.push((project, error.clone()));
-            }
-        }
-
-        if options.show_output {
-            let output = &result.map_or_else(
-                |err| match err {
-                    crate::internal::shell::ShellError::HostProcessExecutionFailure {
-                        command: _,
-                        args: _,
-                        status: _,
-                        stdout,
-                        stderr,
-                    } => format!("{}\n---\n{}", stdout, stderr),
-                    crate::internal::shell::ShellError::ShellStartFailure {
-                        command: _,
-                        args: _,
-                        error,
-                    } => error.to_string(),
-                },
-                |output| output.stdout,
-            );
-
-            info!("Output:\n{}\n---", output);
-        }
-    }
-
-    if !errors.is_empty() {
-        return Err(ForEveryDartProjectError::CommandExecutionError {
-            command,
-            errors: errors
-                .into_iter()
-                .map(|(project, error)| (project.name.clone(), error))
-                .collect(),
-        }
-        .into());
-    }
-
-    dir_utils
-        .popd()
-        .context("trying to navigate back to original path")?;
-
-    Ok(())
-}
+use crate::internal::shell::ShellCommand;
+use anyhow::Context;
+use clap::Args;
+use std::{env, num::NonZeroUsize, path::PathBuf};
+use thiserror::Error;
+
+use super::{
+    describe_failed_projects,
+    workspace_runner::{ProjectKind, WorkspaceOptions, WorkspaceRunner},
+    InternalCommandOptions, ProjectFailure,
+};
+
+#[derive(Args, Debug)]
+pub struct ForEveryDartProjectOptions {
+    /// The command to run on each Dart project.
+    #[arg()]
+    command: Vec<String>,
+
+    /// Include Flutter projects when searching for Dart projects to run the command on.
+    #[arg(default_value = "true", short, long)]
+    include_flutter_projects: bool,
+
+    /// Options for finding the projects, running the command in them and reporting the results.
+    #[command(flatten)]
+    workspace: WorkspaceOptions,
+
+    /// The maximum number of projects to run the command on concurrently (defaults to 1, unless
+    /// configured otherwise).
+    #[arg(short, long)]
+    jobs: Option<NonZeroUsize>,
+
+    /// Run the command in dependency order, so every project runs after the local projects it
+    /// depends on (through `path:` dependencies). When combined with `--jobs`, projects of the
+    /// same dependency level run concurrently.
+    #[arg(default_value = "false", short, long)]
+    ordered: bool,
+
+    /// Write a JUnit XML report with one test case per project to the given file.
+    #[arg(long, value_name = "FILE")]
+    junit: Option<PathBuf>,
+
+    /// Run the command with `PUB_CACHE` set to the given directory, so projects use a separate
+    /// pub cache.
+    #[arg(long, value_name = "DIR")]
+    pub_cache: Option<PathBuf>,
+
+    /// Run the command with `FLUTTER_ROOT` set to the given Flutter SDK directory.
+    #[arg(long, value_name = "DIR")]
+    flutter_root: Option<PathBuf>,
+}
+
+#[derive(Error, Debug)]
+pub enum ForEveryDartProjectError {
+    /// An error that occurred when trying to execute a command in one or more Dart projects.
+    #[error("command '{command}' failed in {}", describe_failed_projects(.errors))]
+    CommandExecutionError {
+        command: String,
+        errors: Vec<ProjectFailure>,
+    },
+}
+
+pub fn for_every_dart_project(
+    InternalCommandOptions {
+        shell,
+        base_args,
+        config,
+        options,
+    }: InternalCommandOptions<ForEveryDartProjectOptions>,
+) -> anyhow::Result<()> {
+    let command = config.expand_alias(&options.command).join(" ");
+    let runner = WorkspaceRunner::new("ford", shell, base_args, config, &options.workspace)
+        .jobs(options.jobs.unwrap_or(config.jobs.value))
+        .ordered(options.ordered)
+        .junit(options.junit.as_deref());
+
+    // The command runs in the directory of each project, so relative directories are resolved
+    // against the current directory up front.
+    let mut project_env = vec![];
+    for (key, dir) in [
+        ("PUB_CACHE", &options.pub_cache),
+        ("FLUTTER_ROOT", &options.flutter_root),
+    ] {
+        if let Some(dir) = dir {
+            let dir = env::current_dir()
+                .context(format!("trying to resolve {} '{}'", key, dir.display()))?
+                .join(dir);
+            project_env.push((key, dir.to_string_lossy().into_owned()));
+        }
+    }
+
+    let kind = if options.include_flutter_projects {
+        ProjectKind::All
+    } else {
+        ProjectKind::DartOnly
+    };
+    let projects = runner.find_projects(kind)?;
+    if projects.is_empty() {
+        return Ok(());
+    }
+
+    let errors = runner.run(&command, "Running command", &projects, |run| {
+        run.run_command(project_env.iter().fold(
+            ShellCommand::new("bash").args(["-c", &command]),
+            |shell_command, (key, value)| shell_command.env(key, value),
+        ))
+    })?;
+
+    if !errors.is_empty() {
+        return Err(ForEveryDartProjectError::CommandExecutionError { command, errors }.into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::{
+        cli::BaseArgs,
+        commands::{
+            exit_code_of,
+            test_utils::{base_args, create_dart_project, workspace_options},
+            DISCOVERY_FAILED_EXIT_CODE, PROJECTS_FAILED_EXIT_CODE,
+        },
+        config::Config,
+        shell::{Invocation, ScriptedShell, ShellError},
+        utils::{project_filter::ProjectFilterOptions, retry::RetryOptions},
+    };
+    use std::time::Duration;
+
+    fn options(path: &std::path::Path, command: &str) -> ForEveryDartProjectOptions {
+        ForEveryDartProjectOptions {
+            command: vec![command.to_string()],
+            include_flutter_projects: true,
+            workspace: workspace_options(path),
+            jobs: None,
+            ordered: false,
+            junit: None,
+            pub_cache: None,
+            flutter_root: None,
+        }
+    }
+
+    fn run(shell: &ScriptedShell, options: ForEveryDartProjectOptions) -> anyhow::Result<()> {
+        for_every_dart_project(InternalCommandOptions {
+            shell,
+            base_args: &base_args(),
+            config: &Config::default(),
+            options: &options,
+        })
+    }
+
+    #[test]
+    fn runs_command_in_every_project() {
+        let root = tempfile::tempdir().unwrap();
+        let app = create_dart_project(root.path(), "app", "app", true);
+        let core = create_dart_project(root.path(), "packages/core", "core", false);
+
+        let shell = ScriptedShell::new()
+            .expect(Invocation::new("bash", ["-c", "dart pub get"]).in_dir(&app))
+            .expect(Invocation::new("bash", ["-c", "dart pub get"]).in_dir(&core));
+
+        run(&shell, options(root.path(), "dart pub get")).unwrap();
+        shell.assert_all_used();
+    }
+
+    #[test]
+    fn injects_pub_cache_and_flutter_root_into_every_command() {
+        let root = tempfile::tempdir().unwrap();
+        let app = create_dart_project(root.path(), "app", "app", true);
+        let pub_cache = root.path().join("pub-cache");
+        let flutter_root = root.path().join("flutter");
+
+        let shell = ScriptedShell::new().expect(
+            Invocation::new("bash", ["-c", "dart pub get"])
+                .in_dir(&app)
+                .env("PUB_CACHE", pub_cache.to_str().unwrap())
+                .env("FLUTTER_ROOT", flutter_root.to_str().unwrap()),
+        );
+
+        run(
+            &shell,
+            ForEveryDartProjectOptions {
+                pub_cache: Some(pub_cache.clone()),
+                flutter_root: Some(flutter_root.clone()),
+                ..options(root.path(), "dart pub get")
+            },
+        )
+        .unwrap();
+        shell.assert_all_used();
+    }
+
+    #[test]
+    fn reports_failed_projects_and_skips_the_rest_with_fail_fast() {
+        let root = tempfile::tempdir().unwrap();
+        let a = create_dart_project(root.path(), "a", "a", false);
+        create_dart_project(root.path(), "b", "b", false);
+
+        let shell = ScriptedShell::new().expect(
+            Invocation::new("bash", ["-c", "dart test"])
+                .in_dir(&a)
+                .stderr("Some tests failed.\n")
+                .exit_code(1),
+        );
+
+        let options = ForEveryDartProjectOptions {
+            workspace: WorkspaceOptions {
+                fail_fast: true,
+                ..workspace_options(root.path())
+            },
+            ..options(root.path(), "dart test")
+        };
+        let error = run(&shell, options).unwrap_err();
+        assert!(format!("{:#}", error).contains("project 'a'"));
+        shell.assert_all_used();
+    }
+
+    #[test]
+    fn includes_the_attempts_of_retried_projects_in_the_error() {
+        let root = tempfile::tempdir().unwrap();
+        let a = create_dart_project(root.path(), "a", "a", false);
+        let failure = || {
+            Invocation::new("bash", ["-c", "dart pub get"])
+                .in_dir(&a)
+                .stderr("Connection closed\n")
+                .exit_code(69)
+        };
+        let shell = ScriptedShell::new().expect(failure()).expect(failure());
+
+        let options = ForEveryDartProjectOptions {
+            workspace: WorkspaceOptions {
+                retry: RetryOptions {
+                    retries: 1,
+                    ..RetryOptions::default()
+                },
+                ..workspace_options(root.path())
+            },
+            ..options(root.path(), "dart pub get")
+        };
+        let error = run(&shell, options).unwrap_err();
+        assert!(format!("{:#}", error).contains("project 'a' after 2 attempts"));
+        shell.assert_all_used();
+    }
+
+    #[test]
+    fn uses_distinct_exit_codes_for_failed_projects_and_failed_discovery() {
+        let root = tempfile::tempdir().unwrap();
+        let a = create_dart_project(root.path(), "a", "a", false);
+        let root_path = root.path().canonicalize().unwrap();
+
+        let shell = ScriptedShell::new().expect(
+            Invocation::new("bash", ["-c", "dart test"])
+                .in_dir(&a)
+                .exit_code(1),
+        );
+        let error = run(&shell, options(root.path(), "dart test"))
+            .unwrap_err()
+            .context("trying to run a command for every Dart project");
+        assert_eq!(exit_code_of(&error), PROJECTS_FAILED_EXIT_CODE);
+
+        let shell = ScriptedShell::new().expect(
+            Invocation::new(
+                "git",
+                [
+                    "-C",
+                    root_path.to_str().unwrap(),
+                    "rev-parse",
+                    "--show-toplevel",
+                ],
+            )
+            .stderr("fatal: not a git repository\n")
+            .exit_code(128),
+        );
+        let mut options = options(root.path(), "dart test");
+        options.workspace.discovery.filter = ProjectFilterOptions {
+            changed_since: Some("main".to_string()),
+            ..ProjectFilterOptions::default()
+        };
+        let error = run(&shell, options)
+            .unwrap_err()
+            .context("trying to run a command for every Dart project");
+        assert_eq!(exit_code_of(&error), DISCOVERY_FAILED_EXIT_CODE);
+    }
+
+    #[test]
+    fn only_prints_commands_in_dry_run_mode() {
+        let root = tempfile::tempdir().unwrap();
+        create_dart_project(root.path(), "app", "app", true);
+
+        for_every_dart_project(InternalCommandOptions {
+            shell: &ScriptedShell::new(),
+            base_args: &BaseArgs {
+                dry_run: true,
+                ..base_args()
+            },
+            config: &Config::default(),
+            options: &options(root.path(), "dart pub get"),
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn runs_projects_in_parallel() {
+        let root = tempfile::tempdir().unwrap();
+        let projects: Vec<_> = (0..4)
+            .map(|index| {
+                let name = format!("package_{}", index);
+                create_dart_project(root.path(), &name, &name, false)
+            })
+            .collect();
+
+        let shell = projects
+            .iter()
+            .fold(ScriptedShell::new(), |shell, project| {
+                shell.expect(Invocation::new("bash", ["-c", "dart analyze"]).in_dir(project))
+            });
+
+        let options = ForEveryDartProjectOptions {
+            jobs: NonZeroUsize::new(3),
+            ..options(root.path(), "dart analyze")
+        };
+        run(&shell, options).unwrap();
+        shell.assert_all_used();
+    }
+
+    #[test]
+    fn reports_timed_out_projects_and_keeps_running_the_rest() {
+        let root = tempfile::tempdir().unwrap();
+        let a = create_dart_project(root.path(), "a", "a", false);
+        let b = create_dart_project(root.path(), "b", "b", false);
+
+        let shell = ScriptedShell::new()
+            .expect(
+                Invocation::new("bash", ["-c", "flutter pub get"])
+                    .in_dir(&a)
+                    .stdout("Resolving dependencies...\n")
+                    .timed_out(Duration::from_secs(30)),
+            )
+            .expect(Invocation::new("bash", ["-c", "flutter pub get"]).in_dir(&b));
+
+        let options = ForEveryDartProjectOptions {
+            workspace: WorkspaceOptions {
+                timeout: Some(Duration::from_secs(30)),
+                ..workspace_options(root.path())
+            },
+            ..options(root.path(), "flutter pub get")
+        };
+        let error = run(&shell, options).unwrap_err();
+        let ForEveryDartProjectError::CommandExecutionError { errors, .. } =
+            error.downcast_ref().unwrap();
+        assert!(matches!(
+            errors.as_slice(),
+            [ProjectFailure {
+                project,
+                attempts: 1,
+                error: ShellError::Timeout { timeout, .. },
+            }] if project == "a" && *timeout == Duration::from_secs(30)
+        ));
+        shell.assert_all_used();
+    }
+}
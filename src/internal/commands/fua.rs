@@ -1,192 +1,390 @@
-use crate::{
-    exec_on,
-    internal::{
-        shell::ShellError,
-        utils::{
-            dart::{DartProjectMetadata, DartShell},
-            directory_utils::DirectoryUtils,
-        },
-    },
-    progress,
-};
-use anyhow::Context;
-use clap::Args;
-use log::info;
-use std::{ops::Deref, path::PathBuf};
-use thiserror::Error;
-
-use super::InternalCommandOptions;
-
-#[derive(Args, Debug)]
-pub struct FvmUseForEveryFlutterProjectOptions {
-    /// The command to run on each Dart project.
-    #[arg()]
-    version: String,
-
-    /// The path from which to search for Dart projects.
-    #[arg(default_value = ".", short, long)]
-    path: PathBuf,
-
-    /// Whether to force FVM to set the version for every Dart project (even non-Flutter projects).
-    #[arg(default_value = "false", short, long)]
-    include_dart_projects: bool,
-
-    /// Exit the process immediately if any of the commands run on the Dart projects fail.
-    #[arg(default_value = "false", short, long)]
-    fail_fast: bool,
-
-    /// Show the output of the command run on each Dart project.
-    #[arg(default_value = "false", short, long)]
-    show_output: bool,
-}
-
-#[derive(Error, Debug)]
-pub enum FvmUseForEveryFlutterProjectError {
-    /// An error that occurred when trying to execute a command in one or more Dart projects.
-    #[error("error while executing command '{command}' for one or more projects: {errors:?}")]
-    CommandExecutionError {
-        command: String,
-        errors: Vec<(String, ShellError)>,
-    },
-}
-
-pub fn fvm_use_for_every_flutter_project(
-    InternalCommandOptions {
-        shell,
-        base_args: _,
-        options,
-    }: InternalCommandOptions<FvmUseForEveryFlutterProjectOptions>,
-) -> anyhow::Result<()> {
-    let path = options.path.clone();
-    let mut dir_utils = DirectoryUtils::new();
-
-    dir_utils
-        .pushd(&path)
-        .context("trying to navigate to provided path")?;
-
-    let dart_shell = DartShell::new(shell);
-
-    let mut projects = progress!("Finding Dart projects", {
-        dart_shell.find_dart_projects(None).context(format!(
-            "trying to find Dart projects in path '{}'",
-            path.display()
-        ))
-    })?;
-
-    if !options.include_dart_projects {
-        projects.retain(|project| project.is_flutter_project);
-        info!("Found {} Flutter projects", projects.len());
-    } else {
-        info!("Found {} Dart and Flutter projects", projects.len());
-    }
-
-    if projects.is_empty() {
-        info!("No projects found");
-        return Ok(());
-    }
-
-    let mut errors: Vec<(&DartProjectMetadata, ShellError)> = vec![];
-
-    progress!(
-        format!(
-            "Ensuring Flutter version '{}' is installed",
-            options.version
-        )
-        .as_str(),
-        {
-            exec_on!(
-                shell,
-                "bash",
-                "-c",
-                format!("fvm install {}", options.version).as_str()
-            )
-        }
-    )
-    .context(format!(
-        "trying to install Flutter version '{}' using FVM",
-        options.version
-    ))?;
-
-    let command = {
-        let mut command_parts = vec!["fvm", "use", options.version.as_str()];
-        if options.include_dart_projects {
-            command_parts.push("--force");
-        }
-
-        command_parts.join(" ")
-    };
-
-    info!(
-        "Running command '{}' in {} projects...",
-        command,
-        projects.len()
-    );
-
-    for project in projects.iter() {
-        dir_utils.pushd(&project.path).context(format!(
-            "trying to navigate to project path '{}'",
-            project.path.display()
-        ))?;
-
-        let result = progress!(
-            format!(
-                "Setting FVM version in '{}' ('{}')",
-                project.name,
-                project.path.display()
-            )
-            .as_str(),
-            exec_on!(shell, "bash", "-c", &command)
-        );
-
-        if let Err(error) = &result {
-            let error = error.deref().clone();
-            if options.fail_fast {
-                return Err(error).context(format!(
-                    "trying to run command '{}' on project '{}'",
-                    command, project.name
-                ));
-            } else {
-                errors.push((project, error.clone()));
-            }
-        }
-
-        if options.show_output {
-            let output = &result.map_or_else(
-                |err| match err {
-                    crate::internal::shell::ShellError::HostProcessExecutionFailure {
-                        command: _,
-                        args: _,
-                        status: _,
-                        stdout,
-                        stderr,
-                    } => format!("{}\n---\n{}", stdout, stderr),
-                    crate::internal::shell::ShellError::ShellStartFailure {
-                        command: _,
-                        args: _,
-                        error,
-                    } => error.to_string(),
-                },
-                |output| output.stdout,
-            );
-
-            info!("Output:\n{}\n---", output);
-        }
-    }
-
-    if !errors.is_empty() {
-        return Err(FvmUseForEveryFlutterProjectError::CommandExecutionError {
-            command,
-            errors: errors
-                .into_iter()
-                .map(|(project, error)| (project.name.clone(), error))
-                .collect(),
-        }
-        .into());
-    }
-
-    dir_utils
-        .popd()
-        .context("trying to navigate back to original path")?;
-
-    Ok(())
-}
+use crate::{
+    internal::{
+        shell::{Shell, ShellCommand},
+        utils::fvm::{FlutterVersion, FvmConfig},
+    },
+    progress,
+};
+use anyhow::Context;
+use clap::Args;
+use colored::Colorize;
+use log::{debug, info};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use super::{
+    describe_failed_projects,
+    workspace_runner::{ProjectKind, WorkspaceOptions, WorkspaceRunner},
+    InternalCommandOptions, ProjectFailure,
+};
+
+#[derive(Args, Debug)]
+pub struct FvmUseForEveryFlutterProjectOptions {
+    /// The Flutter version (or channel) to use in each project. When not given, the version
+    /// pinned by the FVM config of the `--from` project is used.
+    #[arg(id = "flutter_version", value_name = "VERSION")]
+    version: Option<String>,
+
+    /// Use the version pinned by the FVM config (`.fvmrc` or `.fvm/fvm_config.json`) of the
+    /// project at the given path (defaults to the root of the Git repository).
+    #[arg(long, value_name = "PATH", conflicts_with = "flutter_version")]
+    from: Option<PathBuf>,
+
+    /// Whether to force FVM to set the version for every Dart project (even non-Flutter projects).
+    #[arg(default_value = "false", short, long)]
+    include_dart_projects: bool,
+
+    /// Options for finding the projects, running `fvm use` in them and reporting the results
+    /// (the timeout and retries also apply to `fvm install`).
+    #[command(flatten)]
+    workspace: WorkspaceOptions,
+}
+
+#[derive(Error, Debug)]
+pub enum FvmUseForEveryFlutterProjectError {
+    /// An error that occurred when trying to execute a command in one or more Dart projects.
+    #[error("command '{command}' failed in {}", describe_failed_projects(.errors))]
+    CommandExecutionError {
+        command: String,
+        errors: Vec<ProjectFailure>,
+    },
+}
+
+pub fn fvm_use_for_every_flutter_project(
+    InternalCommandOptions {
+        shell,
+        base_args,
+        config,
+        options,
+    }: InternalCommandOptions<FvmUseForEveryFlutterProjectOptions>,
+) -> anyhow::Result<()> {
+    let runner = WorkspaceRunner::new("fua", shell, base_args, config, &options.workspace);
+    let retry = &options.workspace.retry;
+    let version = resolve_version(shell, options)?;
+
+    let kind = if options.include_dart_projects {
+        ProjectKind::All
+    } else {
+        ProjectKind::FlutterOnly
+    };
+    let projects = runner.find_projects(kind)?;
+    if projects.is_empty() {
+        return Ok(());
+    }
+
+    // Projects that already pin the version are only skipped when their SDK link exists, since
+    // the link is usually ignored by Git (so it is missing in fresh clones).
+    let (up_to_date, projects): (Vec<_>, Vec<_>) =
+        projects
+            .into_iter()
+            .partition(|project| match FvmConfig::read(&project.path) {
+                Ok(Some(config)) if config.flutter == version => {
+                    let linked = FvmConfig::has_sdk_link(&project.path);
+                    if !linked {
+                        debug!(
+                            "'{}' already pins {}, but its Flutter SDK is not linked yet",
+                            project.name, version
+                        );
+                    }
+                    linked
+                }
+                Ok(_) => false,
+                Err(err) => {
+                    debug!("ignoring FVM config of '{}': {:#}", project.name, err);
+                    false
+                }
+            });
+    for project in &up_to_date {
+        info!(
+            "{} '{}' already uses {}",
+            "✔".green(),
+            project.name,
+            version
+        );
+        runner.report_skipped(project);
+    }
+    if projects.is_empty() {
+        info!("All {} projects already use {}", up_to_date.len(), version);
+    } else {
+        let (install_result, install_attempts) = retry.run("'fvm install'", |attempt| {
+            progress!(
+                format!(
+                    "Ensuring {} is installed{}",
+                    version,
+                    retry.describe_attempt(attempt)
+                )
+                .as_str(),
+                {
+                    ShellCommand::new("bash")
+                        .args(["-c", &format!("fvm install {}", version.as_str())])
+                        .current_dir(&options.workspace.discovery.path)
+                        .timeout(options.workspace.timeout)
+                        .run(runner.mutating_shell())
+                }
+            )
+        });
+        install_result.context(format!(
+            "trying to install {} using FVM{}",
+            version,
+            retry.describe_attempt(install_attempts)
+        ))?;
+    }
+
+    let command = {
+        let mut command_parts = vec!["fvm", "use", version.as_str()];
+        if options.include_dart_projects {
+            command_parts.push("--force");
+        }
+
+        command_parts.join(" ")
+    };
+
+    // This also runs when all projects are up to date, so they are part of the summary.
+    let errors = runner.run(&command, "Setting FVM version", &projects, |run| {
+        run.run_command(ShellCommand::new("bash").args(["-c", &command]))
+    })?;
+
+    if !errors.is_empty() {
+        return Err(
+            FvmUseForEveryFlutterProjectError::CommandExecutionError { command, errors }.into(),
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns the version given on the command line, or the version pinned by the FVM config of the
+/// `--from` project (which defaults to the root of the Git repository of the search path).
+fn resolve_version(
+    shell: &dyn Shell,
+    options: &FvmUseForEveryFlutterProjectOptions,
+) -> anyhow::Result<FlutterVersion> {
+    if let Some(version) = &options.version {
+        return FlutterVersion::parse(version);
+    }
+
+    let from = match &options.from {
+        Some(from) => from.clone(),
+        None => find_repository_root(shell, &options.workspace.discovery.path)
+            .context("trying to find the FVM config to read the Flutter version from")?,
+    };
+    let config = FvmConfig::read(&from)?.context(format!(
+        "no FVM config ('.fvmrc' or '.fvm/fvm_config.json') found in '{}'",
+        from.display()
+    ))?;
+    info!("Using {} from '{}'", config.flutter, config.path.display());
+
+    Ok(config.flutter)
+}
+
+/// Returns the root of the Git repository that contains the given path.
+fn find_repository_root(shell: &dyn Shell, path: &Path) -> anyhow::Result<PathBuf> {
+    let output = ShellCommand::new("git")
+        .arg("-C")
+        .arg(path.to_string_lossy())
+        .args(["rev-parse", "--show-toplevel"])
+        .run(shell)
+        .context("trying to find the root of the Git repository")?;
+
+    Ok(PathBuf::from(output.stdout_utf8()?.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::{
+        commands::test_utils::{base_args, create_dart_project, workspace_options},
+        config::Config,
+        shell::{Invocation, ScriptedShell, ShellError},
+        utils::{fvm::FVM_SDK_LINK, retry::RetryOptions},
+    };
+
+    use std::time::Duration;
+
+    fn options(path: &std::path::Path, version: &str) -> FvmUseForEveryFlutterProjectOptions {
+        FvmUseForEveryFlutterProjectOptions {
+            version: Some(version.to_string()),
+            from: None,
+            include_dart_projects: false,
+            workspace: workspace_options(path),
+        }
+    }
+
+    fn run(
+        shell: &ScriptedShell,
+        options: FvmUseForEveryFlutterProjectOptions,
+    ) -> anyhow::Result<()> {
+        fvm_use_for_every_flutter_project(InternalCommandOptions {
+            shell,
+            base_args: &base_args(),
+            config: &Config::default(),
+            options: &options,
+        })
+    }
+
+    #[test]
+    fn sets_version_in_flutter_projects_only() {
+        let root = tempfile::tempdir().unwrap();
+        let app = create_dart_project(root.path(), "app", "app", true);
+        create_dart_project(root.path(), "core", "core", false);
+
+        let shell = ScriptedShell::new()
+            .expect(Invocation::new("bash", ["-c", "fvm install 3.10.0"]).in_dir(root.path()))
+            .expect(Invocation::new("bash", ["-c", "fvm use 3.10.0"]).in_dir(&app));
+
+        run(&shell, options(root.path(), "3.10.0")).unwrap();
+        shell.assert_all_used();
+    }
+
+    #[test]
+    fn forces_version_in_dart_projects() {
+        let root = tempfile::tempdir().unwrap();
+        let app = create_dart_project(root.path(), "app", "app", true);
+        let core = create_dart_project(root.path(), "core", "core", false);
+
+        let shell = ScriptedShell::new()
+            .expect(Invocation::new("bash", ["-c", "fvm install stable"]).in_dir(root.path()))
+            .expect(Invocation::new("bash", ["-c", "fvm use stable --force"]).in_dir(&app))
+            .expect(Invocation::new("bash", ["-c", "fvm use stable --force"]).in_dir(&core));
+
+        let options = FvmUseForEveryFlutterProjectOptions {
+            include_dart_projects: true,
+            ..options(root.path(), "stable")
+        };
+        run(&shell, options).unwrap();
+        shell.assert_all_used();
+    }
+
+    #[test]
+    fn uses_version_of_repository_root_and_skips_projects_already_on_it() {
+        let root = tempfile::tempdir().unwrap();
+        let root_str = root.path().to_str().unwrap();
+        std::fs::write(root.path().join(".fvmrc"), r#"{"flutter": "3.22.0"}"#).unwrap();
+        let app = create_dart_project(root.path(), "app", "app", true);
+        std::fs::create_dir_all(app.join(FVM_SDK_LINK)).unwrap();
+        std::fs::write(
+            app.join(".fvm/fvm_config.json"),
+            r#"{"flutterSdkVersion": "3.22.0"}"#,
+        )
+        .unwrap();
+        let ui = create_dart_project(root.path(), "ui", "ui", true);
+        std::fs::write(ui.join(".fvmrc"), r#"{"flutter": "3.19.0"}"#).unwrap();
+        // Pins the version, but has no SDK link (like in a fresh clone).
+        let web = create_dart_project(root.path(), "web", "web", true);
+        std::fs::write(web.join(".fvmrc"), r#"{"flutter": "3.22.0"}"#).unwrap();
+
+        let shell = ScriptedShell::new()
+            .expect(
+                Invocation::new("git", ["-C", root_str, "rev-parse", "--show-toplevel"])
+                    .stdout(&format!("{}\n", root_str)),
+            )
+            .expect(Invocation::new("bash", ["-c", "fvm install 3.22.0"]).in_dir(root.path()))
+            .expect(Invocation::new("bash", ["-c", "fvm use 3.22.0"]).in_dir(&ui))
+            .expect(Invocation::new("bash", ["-c", "fvm use 3.22.0"]).in_dir(&web));
+
+        let options = FvmUseForEveryFlutterProjectOptions {
+            version: None,
+            ..options(root.path(), "")
+        };
+        run(&shell, options).unwrap();
+        shell.assert_all_used();
+    }
+
+    #[test]
+    fn reads_channel_from_the_given_project() {
+        let root = tempfile::tempdir().unwrap();
+        let app = create_dart_project(root.path(), "app", "app", true);
+        std::fs::write(app.join(".fvmrc"), r#"{"flutter": "Beta"}"#).unwrap();
+        std::fs::create_dir_all(app.join(FVM_SDK_LINK)).unwrap();
+
+        let options = FvmUseForEveryFlutterProjectOptions {
+            version: None,
+            from: Some(app.clone()),
+            ..options(root.path(), "")
+        };
+        run(&ScriptedShell::new(), options).unwrap();
+    }
+
+    #[test]
+    fn fails_when_version_cannot_be_installed() {
+        let root = tempfile::tempdir().unwrap();
+        create_dart_project(root.path(), "app", "app", true);
+
+        let shell = ScriptedShell::new().expect(
+            Invocation::new("bash", ["-c", "fvm install 0.0.0"])
+                .in_dir(root.path())
+                .stderr("Flutter SDK 0.0.0 is not a valid version\n")
+                .exit_code(1),
+        );
+
+        assert!(run(&shell, options(root.path(), "0.0.0")).is_err());
+        shell.assert_all_used();
+    }
+
+    #[test]
+    fn applies_timeout_to_fvm_install() {
+        let root = tempfile::tempdir().unwrap();
+        create_dart_project(root.path(), "app", "app", true);
+
+        let shell = ScriptedShell::new().expect(
+            Invocation::new("bash", ["-c", "fvm install 3.10.0"])
+                .in_dir(root.path())
+                .timed_out(Duration::from_secs(60)),
+        );
+
+        let options = FvmUseForEveryFlutterProjectOptions {
+            workspace: WorkspaceOptions {
+                timeout: Some(Duration::from_secs(60)),
+                ..workspace_options(root.path())
+            },
+            ..options(root.path(), "3.10.0")
+        };
+        let error = run(&shell, options).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(ShellError::Timeout { .. })
+        ));
+        shell.assert_all_used();
+    }
+
+    #[test]
+    fn retries_commands_whose_stderr_matches_retry_on() {
+        let root = tempfile::tempdir().unwrap();
+        let app = create_dart_project(root.path(), "app", "app", true);
+
+        let shell = ScriptedShell::new()
+            .expect(
+                Invocation::new("bash", ["-c", "fvm install 3.10.0"])
+                    .in_dir(root.path())
+                    .stderr("Connection reset by peer\n")
+                    .exit_code(1),
+            )
+            .expect(Invocation::new("bash", ["-c", "fvm install 3.10.0"]).in_dir(root.path()))
+            .expect(
+                Invocation::new("bash", ["-c", "fvm use 3.10.0"])
+                    .in_dir(&app)
+                    .stderr("Permission denied\n")
+                    .exit_code(1),
+            );
+
+        let options = FvmUseForEveryFlutterProjectOptions {
+            workspace: WorkspaceOptions {
+                retry: RetryOptions {
+                    retries: 2,
+                    retry_on: Some(regex::Regex::new("(?i)connection").unwrap()),
+                    ..RetryOptions::default()
+                },
+                ..workspace_options(root.path())
+            },
+            ..options(root.path(), "3.10.0")
+        };
+        let error = run(&shell, options).unwrap_err();
+        let FvmUseForEveryFlutterProjectError::CommandExecutionError { errors, .. } =
+            error.downcast_ref().unwrap();
+        assert!(matches!(
+            errors.as_slice(),
+            [ProjectFailure { project, attempts: 1, .. }] if project == "app"
+        ));
+        shell.assert_all_used();
+    }
+}
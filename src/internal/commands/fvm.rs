@@ -0,0 +1,343 @@
+use crate::internal::{
+    report::{FvmStatusRecord, OutputFormat, Record, Reporter},
+    utils::{dart::DartProjectMetadata, fvm::FvmConfig, table::render_table},
+};
+use clap::{Args, Subcommand};
+use log::{info, warn};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+use super::{
+    workspace_runner::{ProjectDiscoveryOptions, ProjectKind},
+    InternalCommandOptions,
+};
+
+#[derive(Args, Debug)]
+pub struct FvmOptions {
+    #[command(subcommand)]
+    command: FvmCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum FvmCommand {
+    /// Show the Flutter version pinned by FVM and the SDK constraints of every Dart project in
+    /// the given path, and fail when the projects pin different Flutter versions.
+    ///
+    /// Differing `environment.flutter` and `environment.sdk` constraints are marked as drift too,
+    /// but do not fail the command: packages often deliberately support a wider range of SDKs
+    /// than the apps that use them, as long as the range includes the pinned version.
+    Status(FvmStatusOptions),
+}
+
+#[derive(Args, Debug)]
+pub struct FvmStatusOptions {
+    /// Options for finding the projects to show.
+    #[command(flatten)]
+    discovery: ProjectDiscoveryOptions,
+}
+
+#[derive(Error, Debug)]
+pub enum FvmStatusError {
+    /// The projects pin different Flutter versions using FVM.
+    #[error("the projects pin different Flutter versions using FVM ({})", .versions.join(", "))]
+    Drift { versions: Vec<String> },
+}
+
+/// The field of `FvmStatusRecord` that fails the command when it drifts.
+const FVM_VERSION: &str = "fvm_version";
+
+/// The fields of `FvmStatusRecord` whose drift is only reported, along with how they are
+/// described in messages. Projects can have good reasons for different constraints (like Dart
+/// packages that support older SDKs), so these never fail the command.
+const CONSTRAINTS: [(&str, &str); 2] = [
+    ("flutter_constraint", "Flutter constraints"),
+    ("sdk_constraint", "SDK constraints"),
+];
+
+pub fn fvm(
+    InternalCommandOptions {
+        shell,
+        base_args,
+        config,
+        options,
+    }: InternalCommandOptions<FvmOptions>,
+) -> anyhow::Result<()> {
+    match &options.command {
+        FvmCommand::Status(options) => fvm_status(InternalCommandOptions {
+            shell,
+            base_args,
+            config,
+            options,
+        }),
+    }
+}
+
+pub fn fvm_status(
+    InternalCommandOptions {
+        shell,
+        base_args,
+        config,
+        options,
+    }: InternalCommandOptions<FvmStatusOptions>,
+) -> anyhow::Result<()> {
+    let path = &options.discovery.path;
+    let projects = options
+        .discovery
+        .find_projects(shell, config, ProjectKind::All)?;
+
+    let records = status_records(&projects);
+
+    if base_args.output == OutputFormat::Text {
+        let root = path.canonicalize().unwrap_or_else(|_| path.clone());
+        let rows: Vec<[String; 5]> = records
+            .iter()
+            .map(|record| {
+                // Pinned versions that differ from what most projects use are marked with `(!)`,
+                // constraints (which are only informational) with `(*)`.
+                let describe = |field: &str, value: &Option<String>| match value {
+                    Some(value) if record.drift.contains(&field) => {
+                        let marker = if field == FVM_VERSION { "!" } else { "*" };
+                        format!("{} ({})", value, marker)
+                    }
+                    Some(value) => value.clone(),
+                    None => "-".to_string(),
+                };
+                let relative_path = record.path.strip_prefix(&root).unwrap_or(&record.path);
+                [
+                    record.name.clone(),
+                    match relative_path.as_os_str().is_empty() {
+                        true => ".".to_string(),
+                        false => relative_path.display().to_string(),
+                    },
+                    describe(FVM_VERSION, &record.fvm_version),
+                    describe("flutter_constraint", &record.flutter_constraint),
+                    describe("sdk_constraint", &record.sdk_constraint),
+                ]
+            })
+            .collect();
+
+        for line in render_table(["PROJECT", "PATH", "FVM", "FLUTTER", "SDK"], &rows) {
+            info!("{}", line);
+        }
+    } else {
+        let reporter = Reporter::new(base_args.output);
+        for record in &records {
+            reporter.report(Record::FvmStatus(record.clone()));
+        }
+    }
+
+    let differing_constraints: Vec<&str> = CONSTRAINTS
+        .iter()
+        .filter(|(field, _)| records.iter().any(|record| record.drift.contains(field)))
+        .map(|(_, description)| *description)
+        .collect();
+    if !differing_constraints.is_empty() {
+        info!(
+            "The projects use different {} (marked with (*)), which is allowed",
+            differing_constraints.join(" and ")
+        );
+    }
+
+    let versions: Vec<String> = distinct_values(&records, FVM_VERSION).into_keys().collect();
+    if versions.len() > 1 {
+        return Err(FvmStatusError::Drift { versions }.into());
+    }
+
+    match versions.first() {
+        Some(version) => info!(
+            "All projects that use FVM pin Flutter version '{}'",
+            version
+        ),
+        None => warn!("None of the projects pin a Flutter version using FVM"),
+    }
+
+    Ok(())
+}
+
+/// Creates the status records of the given projects, where every field that differs from the
+/// value used by most projects (that have a value for it) is marked as drift.
+fn status_records(projects: &[DartProjectMetadata]) -> Vec<FvmStatusRecord> {
+    let mut records: Vec<FvmStatusRecord> = projects
+        .iter()
+        .map(|project| FvmStatusRecord {
+            name: project.name.clone(),
+            path: project.path.clone(),
+            fvm_version: match FvmConfig::read(&project.path) {
+                Ok(config) => config.map(|config| config.flutter.as_str().to_string()),
+                Err(err) => {
+                    warn!("{:#}", err);
+                    None
+                }
+            },
+            flutter_constraint: project.pubspec.environment.flutter.clone(),
+            sdk_constraint: project.pubspec.environment.sdk.clone(),
+            drift: vec![],
+        })
+        .collect();
+
+    let fields = std::iter::once(FVM_VERSION).chain(CONSTRAINTS.map(|(field, _)| field));
+    for field in fields {
+        let counts = distinct_values(&records, field);
+        if counts.len() < 2 {
+            continue;
+        }
+
+        // The first of the most common values (in sorted order) is considered the expected one.
+        let expected = counts
+            .iter()
+            .rev()
+            .max_by_key(|(_, count)| **count)
+            .map(|(value, _)| value.clone());
+        for record in &mut records {
+            let value = comparable_value(record, field);
+            if value.is_some() && value != expected {
+                record.drift.push(field);
+            }
+        }
+    }
+
+    records
+}
+
+/// Counts how many records have each (comparable) value of the given field, leaving out records
+/// without a value.
+fn distinct_values(records: &[FvmStatusRecord], field: &str) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for value in records
+        .iter()
+        .filter_map(|record| comparable_value(record, field))
+    {
+        *counts.entry(value).or_default() += 1;
+    }
+    counts
+}
+
+/// Returns the value of the given field of the record in a form that can be compared with other
+/// records, where constraints are normalized so that equivalent ranges are equal.
+fn comparable_value(record: &FvmStatusRecord, field: &str) -> Option<String> {
+    match field {
+        FVM_VERSION => record.fvm_version.clone(),
+        "flutter_constraint" => record
+            .flutter_constraint
+            .as_deref()
+            .map(normalize_constraint),
+        _ => record.sdk_constraint.as_deref().map(normalize_constraint),
+    }
+}
+
+/// Normalizes a version constraint, so that a caret constraint (`^3.4.0`) equals the range it is
+/// short for (`>=3.4.0 <4.0.0`).
+fn normalize_constraint(constraint: &str) -> String {
+    let constraint = constraint.split_whitespace().collect::<Vec<_>>().join(" ");
+    let Some(version) = constraint.strip_prefix('^') else {
+        return constraint;
+    };
+
+    let parts: Option<Vec<u64>> = version.split('.').map(|part| part.parse().ok()).collect();
+    match parts.as_deref() {
+        Some([0, minor, _]) => format!(">={} <0.{}.0", version, minor + 1),
+        Some([major, _, _]) => format!(">={} <{}.0.0", version, major + 1),
+        _ => constraint,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::{
+        commands::test_utils::{base_args, create_project_with_pubspec},
+        config::Config,
+        shell::ScriptedShell,
+    };
+    use std::{fs, path::Path};
+
+    fn create_project(root: &Path, name: &str, fvm: Option<&str>, sdk: &str) {
+        let path = create_project_with_pubspec(
+            root,
+            name,
+            &format!(
+                "name: {}\nenvironment:\n  sdk: \"{}\"\n  flutter: \">=3.22.0\"\n",
+                name, sdk
+            ),
+        );
+        if let Some(fvm) = fvm {
+            fs::write(path.join(".fvmrc"), format!(r#"{{"flutter": "{}"}}"#, fvm)).unwrap();
+        }
+    }
+
+    fn run(root: &Path) -> anyhow::Result<()> {
+        fvm_status(InternalCommandOptions {
+            shell: &ScriptedShell::new(),
+            base_args: &base_args(),
+            config: &Config::default(),
+            options: &FvmStatusOptions {
+                discovery: ProjectDiscoveryOptions {
+                    path: root.to_path_buf(),
+                    ..ProjectDiscoveryOptions::default()
+                },
+            },
+        })
+    }
+
+    fn drift(root: &Path) -> Vec<(String, Vec<&'static str>)> {
+        let projects = ProjectDiscoveryOptions {
+            path: root.to_path_buf(),
+            ..ProjectDiscoveryOptions::default()
+        }
+        .find_projects(&ScriptedShell::new(), &Config::default(), ProjectKind::All)
+        .unwrap();
+
+        status_records(&projects)
+            .into_iter()
+            .map(|record| (record.name, record.drift))
+            .collect()
+    }
+
+    #[test]
+    fn only_reports_differing_constraints() {
+        let root = tempfile::tempdir().unwrap();
+        create_project(root.path(), "app", Some("3.22.0"), "^3.4.0");
+        create_project(root.path(), "ui", Some("3.22.0"), ">=3.4.0 <4.0.0");
+        create_project(root.path(), "core", None, "^2.19.0");
+
+        run(root.path()).unwrap();
+        assert_eq!(
+            drift(root.path()),
+            [
+                ("app".to_string(), vec![]),
+                ("core".to_string(), vec!["sdk_constraint"]),
+                ("ui".to_string(), vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn fails_when_projects_pin_different_versions() {
+        let root = tempfile::tempdir().unwrap();
+        create_project(root.path(), "app", Some("3.22.0"), "^3.4.0");
+        create_project(root.path(), "ui", Some("3.22.0"), "^3.4.0");
+        create_project(root.path(), "legacy", Some("3.19.0"), "^3.4.0");
+
+        let error = run(root.path()).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(FvmStatusError::Drift { versions }) if versions == &["3.19.0", "3.22.0"]
+        ));
+        assert_eq!(
+            drift(root.path()),
+            [
+                ("app".to_string(), vec![]),
+                ("legacy".to_string(), vec!["fvm_version"]),
+                ("ui".to_string(), vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn normalizes_caret_constraints() {
+        assert_eq!(normalize_constraint("^3.4.0"), ">=3.4.0 <4.0.0");
+        assert_eq!(normalize_constraint("^0.2.1"), ">=0.2.1 <0.3.0");
+        assert_eq!(normalize_constraint(">=3.4.0  <4.0.0"), ">=3.4.0 <4.0.0");
+        assert_eq!(normalize_constraint("^3.4.0-0.dev"), "^3.4.0-0.dev");
+    }
+}
@@ -1,84 +1,578 @@
-use crate::{exec_on, internal::shell::ShellError};
-use anyhow::Context;
-use clap::Args;
-use std::path::PathBuf;
-use thiserror::Error;
-
-use super::InternalCommandOptions;
-
-#[derive(Args)]
-pub struct GitHubOpenOptions {
-    /// The path of the project for which to open the Git repository.
-    #[arg(default_value = ".")]
-    path: PathBuf,
-}
-
-#[derive(Error, Debug)]
-pub enum GitHubOpenError {
-    #[error("path '{path}' does not exist or is not an accessible directory")]
-    PathDoesNotExist { path: PathBuf },
-
-    #[error("path '{path}' is not a Git repository")]
-    PathNotAGitRepository { path: PathBuf },
-
-    #[error("project at path '{path}' has no remotes configured")]
-    NoRemotesConfigured { path: PathBuf },
-}
-
-pub fn git_hub_open(
-    InternalCommandOptions {
-        shell,
-        base_args: _,
-        options,
-    }: InternalCommandOptions<GitHubOpenOptions>,
-) -> anyhow::Result<()> {
-    let path = options.path.clone();
-    let path_str = path.to_str().unwrap();
-
-    if !path.exists() || !path.is_dir() {
-        return Err(GitHubOpenError::PathDoesNotExist { path }.into());
-    }
-
-    let is_git_dir = exec_on!(shell, "git", "-C", path_str, "rev-parse", "--git-dir")
-        .map_or_else(
-            |err| match err {
-                ShellError::HostProcessExecutionFailure {
-                    command: _,
-                    args: _,
-                    status,
-                    stdout: _,
-                    stderr: _,
-                } if status.code() == Some(128) => Ok(false),
-                _ => Err(err),
-            },
-            |output| Ok(!output.stdout.trim().is_empty()),
-        )
-        .context("checking whether current dir is a git repository")?;
-
-    if !is_git_dir {
-        return Err(GitHubOpenError::PathNotAGitRepository { path }.into());
-    }
-
-    let remote_branches = exec_on!(shell, "git", "-C", path_str, "branch", "-r")
-        .map(|output| output.stdout.trim().to_string())?;
-
-    if remote_branches.is_empty() {
-        return Err(GitHubOpenError::NoRemotesConfigured { path }.into());
-    }
-
-    let remote_url = exec_on!(
-        shell,
-        "git",
-        "-C",
-        path_str,
-        "config",
-        "--get",
-        "remote.origin.url"
-    )
-    .map(|output| output.stdout.trim().to_string())
-    .context("trying to fetch the remote url")?;
-
-    exec_on!(shell, "open", remote_url)?;
-
-    Ok(())
-}
+use crate::{
+    exec_on,
+    internal::{
+        report::{OutputFormat, Record, Reporter},
+        shell::{DryRunShell, Shell, ShellError},
+        utils::{
+            browser::BrowserLauncher,
+            git_remote::{GitRef, LineRange, RemoteRepository, WebPage},
+        },
+    },
+};
+use anyhow::Context;
+use clap::Args;
+use log::debug;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use super::InternalCommandOptions;
+
+#[derive(Args)]
+pub struct GitHubOpenOptions {
+    /// The path of the project, directory or file to open in the browser.
+    #[arg(default_value = ".")]
+    path: PathBuf,
+
+    /// The line or range of lines to highlight when opening a file (e.g. `10` or `10-20`).
+    #[arg(short = 'l', long = "line", value_name = "LINES")]
+    lines: Option<LineRange>,
+
+    /// Open the given commit (or the file or directory at the given commit) instead of the
+    /// current branch.
+    #[arg(long, value_name = "SHA", conflicts_with_all = ["pr", "compare", "actions", "issues"])]
+    commit: Option<String>,
+
+    /// Open the pull request (or merge request) page for the current branch.
+    #[arg(long, conflicts_with_all = ["compare", "actions", "issues", "lines"])]
+    pr: bool,
+
+    /// Open the comparison of the current branch against the default branch.
+    #[arg(long, conflicts_with_all = ["actions", "issues", "lines"])]
+    compare: bool,
+
+    /// Open the CI page (e.g. GitHub Actions or GitLab pipelines) of the repository.
+    #[arg(long, conflicts_with_all = ["issues", "lines"])]
+    actions: bool,
+
+    /// Open the issues page of the repository.
+    #[arg(long, conflicts_with = "lines")]
+    issues: bool,
+
+    /// The remote to open (defaults to the remote tracked by the current branch, then `origin`,
+    /// then the only configured remote).
+    #[arg(short = 'r', long, value_name = "NAME")]
+    remote: Option<String>,
+
+    /// Only print the URL to stdout instead of opening it in a browser.
+    #[arg(long)]
+    print: bool,
+}
+
+#[derive(Error, Debug)]
+pub enum GitHubOpenError {
+    #[error("path '{path}' does not exist or is not accessible")]
+    PathDoesNotExist { path: PathBuf },
+
+    #[error("path '{path}' is not a Git repository")]
+    PathNotAGitRepository { path: PathBuf },
+
+    #[error("project at path '{path}' has no remotes configured")]
+    NoRemotesConfigured { path: PathBuf },
+
+    #[error("remote '{remote}' does not exist (available remotes: {})", .remotes.join(", "))]
+    RemoteNotFound {
+        remote: String,
+        remotes: Vec<String>,
+    },
+
+    #[error("could not choose a remote for project at path '{path}', use --remote to pick one of: {}", .remotes.join(", "))]
+    AmbiguousRemote { path: PathBuf, remotes: Vec<String> },
+
+    #[error("--line can only be used when opening a file, but '{path}' is a directory")]
+    LinesWithoutFile { path: PathBuf },
+
+    #[error("project at path '{path}' is not on a branch (HEAD is detached)")]
+    DetachedHead { path: PathBuf },
+
+    #[error("could not determine the default branch of remote '{remote}' (try running `git remote set-head {remote} --auto`)")]
+    UnknownDefaultBranch { remote: String },
+
+    #[error("{page} cannot be opened for repositories hosted on {provider}")]
+    PageNotSupported { page: String, provider: String },
+}
+
+pub fn git_hub_open(
+    InternalCommandOptions {
+        shell,
+        base_args,
+        config,
+        options,
+    }: InternalCommandOptions<GitHubOpenOptions>,
+) -> anyhow::Result<()> {
+    let path = options.path.clone();
+    let dry_run_shell = DryRunShell::new(base_args.output);
+    let mutating_shell: &dyn Shell = if base_args.dry_run {
+        &dry_run_shell
+    } else {
+        shell
+    };
+
+    if !path.exists() {
+        return Err(GitHubOpenError::PathDoesNotExist { path }.into());
+    }
+
+    let directory = if path.is_dir() {
+        path.clone()
+    } else {
+        match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        }
+    };
+    let directory_str: &str = &directory.to_string_lossy();
+
+    let top_level = exec_on!(
+        shell,
+        "git",
+        "-C",
+        directory_str,
+        "rev-parse",
+        "--show-toplevel"
+    )
+    .map_or_else(
+        |err| match err {
+            ShellError::HostProcessExecutionFailure {
+                command: _,
+                args: _,
+                status,
+                stdout: _,
+                stderr: _,
+            } if status.code() == Some(128) => Ok(None),
+            _ => Err(err),
+        },
+        |output| {
+            let top_level = output.stdout_utf8()?.trim();
+            Ok((!top_level.is_empty()).then(|| PathBuf::from(top_level)))
+        },
+    )
+    .context("checking whether current dir is a git repository")?;
+
+    let Some(top_level) = top_level else {
+        return Err(GitHubOpenError::PathNotAGitRepository { path }.into());
+    };
+
+    let remotes: Vec<String> = exec_on!(shell, "git", "-C", directory_str, "remote")
+        .and_then(|output| {
+            Ok(output
+                .stdout_utf8()?
+                .lines()
+                .map(|line| line.trim().to_string())
+                .collect())
+        })
+        .context("trying to list the remotes")?;
+
+    if remotes.is_empty() {
+        return Err(GitHubOpenError::NoRemotesConfigured { path }.into());
+    }
+
+    // The current branch (or commit) is used for choosing the remote as well as for the page.
+    let current_ref = current_ref(shell, directory_str)?;
+    let remote = select_remote(
+        shell,
+        directory_str,
+        &path,
+        &remotes,
+        &current_ref,
+        options.remote.as_deref(),
+    )?;
+    debug!("using remote '{}'", remote);
+
+    let remote_url = exec_on!(
+        shell,
+        "git",
+        "-C",
+        directory_str,
+        "config",
+        "--get",
+        format!("remote.{}.url", remote)
+    )
+    .and_then(|output| Ok(output.stdout_utf8()?.trim().to_string()))
+    .context("trying to fetch the remote url")?;
+
+    let repository = RemoteRepository::parse(&remote_url)?;
+    debug!(
+        "remote url '{}' points to {} repository at '{}'",
+        remote_url,
+        repository.provider,
+        repository.web_url()
+    );
+
+    let relative_path = relative_path(&top_level, &path)?;
+    let page = if options.pr {
+        WebPage::PullRequest {
+            base: default_branch(shell, directory_str, &remote)?,
+            head: current_branch(&current_ref, &path)?,
+        }
+    } else if options.compare {
+        WebPage::Compare {
+            base: default_branch(shell, directory_str, &remote)?,
+            head: current_branch(&current_ref, &path)?,
+        }
+    } else if options.actions {
+        WebPage::Actions
+    } else if options.issues {
+        WebPage::Issues
+    } else if path.is_dir() {
+        if options.lines.is_some() {
+            return Err(GitHubOpenError::LinesWithoutFile { path }.into());
+        }
+
+        match &options.commit {
+            Some(sha) if relative_path.is_empty() => WebPage::Commit { sha: sha.clone() },
+            Some(sha) => WebPage::Tree {
+                git_ref: GitRef::Commit(sha.clone()),
+                path: relative_path.clone(),
+            },
+            None => WebPage::Tree {
+                git_ref: current_ref.clone(),
+                path: relative_path.clone(),
+            },
+        }
+    } else {
+        WebPage::File {
+            git_ref: match &options.commit {
+                Some(sha) => GitRef::Commit(sha.clone()),
+                None => current_ref.clone(),
+            },
+            path: relative_path.clone(),
+            lines: options.lines,
+        }
+    };
+
+    let is_default_page = matches!(&page, WebPage::Tree { path, .. } if path.is_empty());
+    let url = match repository.page_url(&page) {
+        Some(url) => url,
+        // Without any explicit flags, fall back to the repository root for unknown providers.
+        None if is_default_page => repository.web_url(),
+        None => {
+            return Err(GitHubOpenError::PageNotSupported {
+                page: page.to_string(),
+                provider: repository.provider.to_string(),
+            }
+            .into())
+        }
+    };
+
+    Reporter::new(base_args.output).report(Record::GitHubOpen { url: url.clone() });
+
+    if options.print {
+        // In other output formats, the url is already part of the reported record.
+        if base_args.output == OutputFormat::Text {
+            println!("{}", url);
+        }
+        return Ok(());
+    }
+
+    let custom_browser = config
+        .browser
+        .as_ref()
+        .map(|browser| browser.value.as_str());
+    BrowserLauncher::new(custom_browser).open(mutating_shell, &url)?;
+
+    Ok(())
+}
+
+/// Returns the path of `path` relative to the repository root, using `/` as separator.
+fn relative_path(top_level: &Path, path: &Path) -> anyhow::Result<String> {
+    let top_level = top_level
+        .canonicalize()
+        .context("trying to resolve the root of the Git repository")?;
+    let path = path
+        .canonicalize()
+        .context(format!("trying to resolve path '{}'", path.display()))?;
+
+    let relative_path = path.strip_prefix(&top_level).context(format!(
+        "path '{}' is not inside the Git repository at '{}'",
+        path.display(),
+        top_level.display()
+    ))?;
+
+    Ok(relative_path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/"))
+}
+
+/// Returns the remote to open: the requested one, or otherwise the remote tracked by the current
+/// branch, `origin`, or the only remote (in that order).
+fn select_remote(
+    shell: &dyn Shell,
+    directory: &str,
+    path: &Path,
+    remotes: &[String],
+    current_ref: &GitRef,
+    requested: Option<&str>,
+) -> anyhow::Result<String> {
+    if let Some(remote) = requested {
+        if !remotes.iter().any(|candidate| candidate == remote) {
+            return Err(GitHubOpenError::RemoteNotFound {
+                remote: remote.to_string(),
+                remotes: remotes.to_vec(),
+            }
+            .into());
+        }
+        return Ok(remote.to_string());
+    }
+
+    if let GitRef::Branch(branch) = current_ref {
+        let tracking_remote = exec_on!(
+            shell,
+            "git",
+            "-C",
+            directory,
+            "config",
+            "--get",
+            format!("branch.{}.remote", branch)
+        )
+        .and_then(|output| Ok(output.stdout_utf8()?.trim().to_string()));
+
+        if let Ok(tracking_remote) = tracking_remote {
+            if remotes.contains(&tracking_remote) {
+                return Ok(tracking_remote);
+            }
+        }
+    }
+
+    if remotes.iter().any(|remote| remote == "origin") {
+        return Ok("origin".to_string());
+    }
+
+    match remotes {
+        [remote] => Ok(remote.clone()),
+        _ => Err(GitHubOpenError::AmbiguousRemote {
+            path: path.to_path_buf(),
+            remotes: remotes.to_vec(),
+        }
+        .into()),
+    }
+}
+
+/// Returns the current branch, or the current commit if HEAD is detached.
+fn current_ref(shell: &dyn Shell, directory: &str) -> anyhow::Result<GitRef> {
+    let branch = exec_on!(
+        shell,
+        "git",
+        "-C",
+        directory,
+        "rev-parse",
+        "--abbrev-ref",
+        "HEAD"
+    )
+    .and_then(|output| Ok(output.stdout_utf8()?.trim().to_string()))
+    .context("trying to find the current branch")?;
+
+    if branch != "HEAD" {
+        return Ok(GitRef::Branch(branch));
+    }
+
+    let sha = exec_on!(shell, "git", "-C", directory, "rev-parse", "HEAD")
+        .and_then(|output| Ok(output.stdout_utf8()?.trim().to_string()))
+        .context("trying to find the current commit")?;
+
+    Ok(GitRef::Commit(sha))
+}
+
+/// Returns the branch of the given current ref, failing if HEAD is detached.
+fn current_branch(current_ref: &GitRef, path: &Path) -> anyhow::Result<String> {
+    match current_ref {
+        GitRef::Branch(branch) => Ok(branch.clone()),
+        GitRef::Commit(_) => Err(GitHubOpenError::DetachedHead {
+            path: path.to_path_buf(),
+        }
+        .into()),
+    }
+}
+
+/// Returns the default branch of the given remote, as recorded in `refs/remotes/<remote>/HEAD`,
+/// falling back to `main` or `master` if the remote has such a branch.
+fn default_branch(shell: &dyn Shell, directory: &str, remote: &str) -> anyhow::Result<String> {
+    let remote_head = exec_on!(
+        shell,
+        "git",
+        "-C",
+        directory,
+        "symbolic-ref",
+        "--quiet",
+        "--short",
+        format!("refs/remotes/{}/HEAD", remote)
+    );
+
+    if let Ok(output) = remote_head {
+        let remote_head = output.stdout_utf8()?.trim();
+        let branch = remote_head
+            .strip_prefix(&format!("{}/", remote))
+            .unwrap_or(remote_head);
+        return Ok(branch.to_string());
+    }
+
+    for branch in ["main", "master"] {
+        let exists = exec_on!(
+            shell,
+            "git",
+            "-C",
+            directory,
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            format!("refs/remotes/{}/{}", remote, branch)
+        )
+        .is_ok();
+
+        if exists {
+            debug!(
+                "remote '{}' has no HEAD, assuming '{}' is the default branch",
+                remote, branch
+            );
+            return Ok(branch.to_string());
+        }
+    }
+
+    Err(GitHubOpenError::UnknownDefaultBranch {
+        remote: remote.to_string(),
+    }
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::{
+        commands::test_utils::base_args,
+        config::{Config, ConfigSource, Sourced},
+        shell::{Invocation, ScriptedShell},
+    };
+
+    fn options(path: &Path) -> GitHubOpenOptions {
+        GitHubOpenOptions {
+            path: path.to_path_buf(),
+            lines: None,
+            commit: None,
+            pr: false,
+            compare: false,
+            actions: false,
+            issues: false,
+            remote: None,
+            print: false,
+        }
+    }
+
+    fn config() -> Config {
+        Config {
+            browser: Some(Sourced {
+                value: "test-browser".to_string(),
+                source: ConfigSource::Default,
+            }),
+            ..Config::default()
+        }
+    }
+
+    fn git(directory: &Path, args: &[&str]) -> Invocation {
+        let mut git_args = vec!["-C", directory.to_str().unwrap()];
+        git_args.extend(args);
+        Invocation::new("git", git_args)
+    }
+
+    fn run(shell: &ScriptedShell, options: GitHubOpenOptions) -> anyhow::Result<()> {
+        git_hub_open(InternalCommandOptions {
+            shell,
+            base_args: &base_args(),
+            config: &config(),
+            options: &options,
+        })
+    }
+
+    #[test]
+    fn opens_current_branch_on_tracking_remote() {
+        let repository = tempfile::tempdir().unwrap();
+        let dir = repository.path().canonicalize().unwrap();
+        let dir_str = dir.to_str().unwrap();
+
+        let shell = ScriptedShell::new()
+            .expect(git(&dir, &["rev-parse", "--show-toplevel"]).stdout(dir_str))
+            .expect(git(&dir, &["remote"]).stdout("origin\nupstream\n"))
+            .expect(git(&dir, &["rev-parse", "--abbrev-ref", "HEAD"]).stdout("feature/x\n"))
+            .expect(git(&dir, &["config", "--get", "branch.feature/x.remote"]).stdout("upstream\n"))
+            .expect(
+                git(&dir, &["config", "--get", "remote.upstream.url"])
+                    .stdout("git@gitlab.com:group/sub/repo.git\n"),
+            )
+            .expect(Invocation::new(
+                "test-browser",
+                ["https://gitlab.com/group/sub/repo/-/tree/feature/x"],
+            ));
+
+        run(&shell, options(&dir)).unwrap();
+        shell.assert_all_used();
+    }
+
+    #[test]
+    fn opens_file_lines_on_origin() {
+        let repository = tempfile::tempdir().unwrap();
+        let dir = repository.path().canonicalize().unwrap();
+        let lib = dir.join("lib");
+        std::fs::create_dir(&lib).unwrap();
+        std::fs::write(lib.join("main.dart"), "void main() {}\n").unwrap();
+
+        let shell = ScriptedShell::new()
+            .expect(git(&lib, &["rev-parse", "--show-toplevel"]).stdout(dir.to_str().unwrap()))
+            .expect(git(&lib, &["remote"]).stdout("origin\nupstream\n"))
+            .expect(git(&lib, &["rev-parse", "--abbrev-ref", "HEAD"]).stdout("HEAD\n"))
+            .expect(git(&lib, &["rev-parse", "HEAD"]).stdout("abc123\n"))
+            .expect(
+                git(&lib, &["config", "--get", "remote.origin.url"])
+                    .stdout("https://token@github.com/org/repo.git\n"),
+            )
+            .expect(Invocation::new(
+                "test-browser",
+                ["https://github.com/org/repo/blob/abc123/lib/main.dart#L3-L4"],
+            ));
+
+        let options = GitHubOpenOptions {
+            lines: Some("3-4".parse().unwrap()),
+            commit: Some("abc123".to_string()),
+            ..options(&lib.join("main.dart"))
+        };
+        run(&shell, options).unwrap();
+        shell.assert_all_used();
+    }
+
+    #[test]
+    fn fails_when_remote_is_ambiguous() {
+        let repository = tempfile::tempdir().unwrap();
+        let dir = repository.path().canonicalize().unwrap();
+
+        let shell = ScriptedShell::new()
+            .expect(git(&dir, &["rev-parse", "--show-toplevel"]).stdout(dir.to_str().unwrap()))
+            .expect(git(&dir, &["remote"]).stdout("fork\nupstream\n"))
+            .expect(git(&dir, &["rev-parse", "--abbrev-ref", "HEAD"]).stdout("main\n"))
+            .expect(git(&dir, &["config", "--get", "branch.main.remote"]).exit_code(1));
+
+        let error = run(&shell, options(&dir)).unwrap_err();
+        match error.downcast_ref::<GitHubOpenError>() {
+            Some(GitHubOpenError::AmbiguousRemote { remotes, .. }) => {
+                assert_eq!(remotes, &["fork", "upstream"])
+            }
+            _ => panic!("expected an AmbiguousRemote error, got {:?}", error),
+        }
+        shell.assert_all_used();
+    }
+
+    #[test]
+    fn fails_when_path_is_not_a_git_repository() {
+        let directory = tempfile::tempdir().unwrap();
+        let dir = directory.path().canonicalize().unwrap();
+
+        let shell = ScriptedShell::new().expect(
+            git(&dir, &["rev-parse", "--show-toplevel"])
+                .stderr("fatal: not a git repository\n")
+                .exit_code(128),
+        );
+
+        let error = run(&shell, options(&dir)).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<GitHubOpenError>(),
+            Some(GitHubOpenError::PathNotAGitRepository { .. })
+        ));
+    }
+}
@@ -1,17 +1,88 @@
-pub mod ford;
-pub mod fua;
-pub mod gho;
-pub mod upgrade;
-
-pub use ford::*;
-pub use fua::*;
-pub use gho::*;
-pub use upgrade::*;
-
-use super::{cli::BaseArgs, shell::Shell};
-
-pub struct InternalCommandOptions<'a, T> {
-    pub shell: &'a Shell,
-    pub base_args: &'a BaseArgs,
-    pub options: &'a T,
-}
+pub mod config;
+pub mod deps;
+pub mod ford;
+pub mod fua;
+pub mod fvm;
+pub mod gho;
+pub mod upgrade;
+pub mod workspace_runner;
+
+#[cfg(test)]
+mod test_utils;
+
+pub use config::*;
+pub use deps::*;
+pub use ford::*;
+pub use fua::*;
+pub use fvm::*;
+pub use gho::*;
+pub use upgrade::*;
+
+use super::{
+    cli::BaseArgs,
+    config::Config,
+    shell::{Shell, ShellError},
+};
+use thiserror::Error;
+
+/// The exit code of suitcase when a command failed in one or more projects.
+pub const PROJECTS_FAILED_EXIT_CODE: u8 = 3;
+
+/// The exit code of suitcase when the projects to run a command in could not be found (or
+/// filtered).
+pub const DISCOVERY_FAILED_EXIT_CODE: u8 = 4;
+
+/// Added as context to errors that occur while finding the projects to run a command in, so
+/// they can be told apart from errors of the command itself.
+#[derive(Error, Debug)]
+#[error("trying to find the projects to run the command in")]
+pub struct DiscoveryError;
+
+/// A command that failed in a single project, as included in the error of commands that run in
+/// multiple projects.
+#[derive(Debug)]
+pub struct ProjectFailure {
+    pub project: String,
+    pub attempts: u32,
+    pub error: ShellError,
+}
+
+/// Returns the exit code for an error returned by a command: `PROJECTS_FAILED_EXIT_CODE` when a
+/// command failed in one or more projects, `DISCOVERY_FAILED_EXIT_CODE` when the projects could
+/// not be found and 1 for all other errors.
+pub fn exit_code_of(error: &anyhow::Error) -> u8 {
+    if error.downcast_ref::<DiscoveryError>().is_some() {
+        DISCOVERY_FAILED_EXIT_CODE
+    } else if error.downcast_ref::<ForEveryDartProjectError>().is_some()
+        || error
+            .downcast_ref::<FvmUseForEveryFlutterProjectError>()
+            .is_some()
+    {
+        PROJECTS_FAILED_EXIT_CODE
+    } else {
+        1
+    }
+}
+
+/// Describes the projects of the given failures for use in error messages (like `project 'a'`
+/// or `2 projects ('a' after 3 attempts, 'b')`).
+fn describe_failed_projects(failures: &[ProjectFailure]) -> String {
+    let names: Vec<String> = failures
+        .iter()
+        .map(|failure| match failure.attempts {
+            0 | 1 => format!("'{}'", failure.project),
+            attempts => format!("'{}' after {} attempts", failure.project, attempts),
+        })
+        .collect();
+    match names.as_slice() {
+        [name] => format!("project {}", name),
+        names => format!("{} projects ({})", names.len(), names.join(", ")),
+    }
+}
+
+pub struct InternalCommandOptions<'a, T> {
+    pub shell: &'a dyn Shell,
+    pub base_args: &'a BaseArgs,
+    pub config: &'a Config,
+    pub options: &'a T,
+}
@@ -0,0 +1,52 @@
+//! Helpers for testing commands with a `ScriptedShell`.
+
+use super::workspace_runner::{ProjectDiscoveryOptions, WorkspaceOptions};
+use crate::internal::{cli::BaseArgs, report::OutputFormat};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Returns the base arguments of a command run without any global flags.
+pub fn base_args() -> BaseArgs {
+    BaseArgs {
+        verbose: false,
+        quiet: false,
+        output: OutputFormat::Text,
+        dry_run: false,
+        strip_ansi: false,
+    }
+}
+
+/// Returns the workspace options of a command that runs in the projects in the given path,
+/// without any other flags.
+pub fn workspace_options(path: &Path) -> WorkspaceOptions {
+    WorkspaceOptions {
+        discovery: ProjectDiscoveryOptions {
+            path: path.to_path_buf(),
+            ..ProjectDiscoveryOptions::default()
+        },
+        ..WorkspaceOptions::default()
+    }
+}
+
+/// Creates a Dart (or Flutter) project with the given package name in `root/folder` and returns
+/// its canonical path.
+pub fn create_dart_project(root: &Path, folder: &str, name: &str, is_flutter: bool) -> PathBuf {
+    let mut pubspec = format!("name: {}\n", name);
+    if is_flutter {
+        pubspec.push_str("dependencies:\n  flutter:\n    sdk: flutter\n");
+    }
+
+    create_project_with_pubspec(root, folder, &pubspec)
+}
+
+/// Creates a project with the given `pubspec.yaml` contents in `root/folder` and returns its
+/// canonical path.
+pub fn create_project_with_pubspec(root: &Path, folder: &str, pubspec: &str) -> PathBuf {
+    let path = root.join(folder);
+    fs::create_dir_all(&path).unwrap();
+    fs::write(path.join("pubspec.yaml"), pubspec).unwrap();
+
+    path.canonicalize().unwrap()
+}
@@ -4,7 +4,15 @@ use anyhow::Context;
 use clap::Args;
 use log::{debug, error, info};
 
-use crate::{exec_on, internal::constants::PACKAGE_NAME, progress};
+use crate::{
+    exec_on,
+    internal::{
+        constants::PACKAGE_NAME,
+        report::{Record, Reporter},
+        shell::{DryRunShell, Shell},
+    },
+    progress,
+};
 
 use super::InternalCommandOptions;
 
@@ -18,10 +26,19 @@ pub struct UpgradeOptions {
 pub fn upgrade(
     InternalCommandOptions {
         shell,
-        base_args: _,
+        base_args,
+        config: _,
         options,
     }: InternalCommandOptions<UpgradeOptions>,
 ) -> anyhow::Result<()> {
+    let reporter = Reporter::new(base_args.output);
+    let dry_run_shell = DryRunShell::new(base_args.output);
+    let mutating_shell: &dyn Shell = if base_args.dry_run {
+        &dry_run_shell
+    } else {
+        shell
+    };
+
     if options.offline {
         info!("Running cargo in offline mode");
     }
@@ -33,9 +50,9 @@ pub fn upgrade(
     .context("trying to get installed cargo packages")?;
 
     let installed_packages = installed_packages_str
-        .stdout
+        .stdout_lossy()
         .lines()
-        .filter(|line| line.chars().next().map_or(false, |c| c.is_alphabetic()))
+        .filter(|line| line.chars().next().is_some_and(|c| c.is_alphabetic()))
         .map(CargoPackageMetadata::from_str)
         .collect::<anyhow::Result<Vec<_>>>()?;
 
@@ -48,8 +65,9 @@ pub fn upgrade(
     );
 
     let Some(this_package) = installed_packages
-    .iter()
-    .find(|package| package.name == PACKAGE_NAME) else {
+        .iter()
+        .find(|package| package.name == PACKAGE_NAME)
+    else {
         anyhow::bail!(
             "could not find {} package in installed packages (all packages: {:?})",
             PACKAGE_NAME,
@@ -72,7 +90,7 @@ pub fn upgrade(
             format!("Upgrading suitcase from local path ({})...", path).as_str(),
             if options.offline {
                 exec_on!(
-                    shell,
+                    mutating_shell,
                     "cargo",
                     "install",
                     "--offline",
@@ -81,7 +99,14 @@ pub fn upgrade(
                     path
                 )
             } else {
-                exec_on!(shell, "cargo", "install", "--force", "--path", path)
+                exec_on!(
+                    mutating_shell,
+                    "cargo",
+                    "install",
+                    "--force",
+                    "--path",
+                    path
+                )
             }
         )
         .context(format!(
@@ -96,18 +121,18 @@ pub fn upgrade(
     } else {
         progress!(
             "Upgrading suitcase from crates.io...",
-            exec_on!(shell, "cargo", "install", "--force", PACKAGE_NAME)
+            exec_on!(mutating_shell, "cargo", "install", "--force", PACKAGE_NAME)
         )
         .context("trying to upgrade suitcase from crates.io")?
     };
 
+    if base_args.dry_run {
+        return Ok(());
+    }
+
     // For some reason, cargo prints to stderr instead of stdout when upgrading a package.
-    let last_line = output
-        .stderr
-        .lines()
-        .map(str::trim)
-        .filter(|line| !line.is_empty())
-        .last();
+    let stderr = output.stderr_lossy();
+    let last_line = stderr.lines().map(str::trim).rfind(|line| !line.is_empty());
 
     if let Some(last_line) = last_line {
         let backticks = last_line.matches('`');
@@ -140,11 +165,23 @@ pub fn upgrade(
                 "Upgraded {} from v{} to v{}",
                 previous_package.name, previous_package.version, new_package.version
             );
+            reporter.report(Record::Upgrade {
+                package: PACKAGE_NAME.to_string(),
+                old_version: Some(previous_package.version),
+                new_version: Some(new_package.version),
+            });
+            return Ok(());
         }
     } else {
         info!("Upgraded suitcase successfully (but failed to parse output from cargo)");
     }
 
+    reporter.report(Record::Upgrade {
+        package: PACKAGE_NAME.to_string(),
+        old_version: Some(this_package.version.clone()),
+        new_version: None,
+    });
+
     Ok(())
 }
 
@@ -178,3 +215,96 @@ impl FromStr for CargoPackageMetadata {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::{
+        cli::BaseArgs,
+        commands::test_utils::base_args,
+        config::Config,
+        shell::{Invocation, ScriptedShell},
+    };
+
+    fn run(shell: &ScriptedShell, offline: bool) -> anyhow::Result<()> {
+        upgrade(InternalCommandOptions {
+            shell,
+            base_args: &base_args(),
+            config: &Config::default(),
+            options: &UpgradeOptions { offline },
+        })
+    }
+
+    #[test]
+    fn upgrades_from_crates_io() {
+        let shell = ScriptedShell::new()
+            .expect(
+                Invocation::new("cargo", ["install", "--list"])
+                    .stdout("ripgrep v13.0.0:\n    rg\nsuitcase v0.1.3:\n    suitcase\n"),
+            )
+            .expect(
+                Invocation::new("cargo", ["install", "--force", "suitcase"]).stderr(
+                    "  Installing suitcase v0.1.4\n   Replaced package `suitcase v0.1.3` with `suitcase v0.1.4` (executables `suitcase`)\n",
+                ),
+            );
+
+        run(&shell, false).unwrap();
+        shell.assert_all_used();
+    }
+
+    #[test]
+    fn upgrades_from_local_path_in_offline_mode() {
+        let shell = ScriptedShell::new()
+            .expect(
+                Invocation::new("cargo", ["install", "--list"])
+                    .stdout("suitcase v0.1.3 (/src/suitcase):\n    suitcase\n"),
+            )
+            .expect(Invocation::new(
+                "cargo",
+                ["install", "--offline", "--force", "--path", "/src/suitcase"],
+            ));
+
+        run(&shell, true).unwrap();
+        shell.assert_all_used();
+    }
+
+    #[test]
+    fn only_lists_packages_in_dry_run_mode() {
+        let shell = ScriptedShell::new().expect(
+            Invocation::new("cargo", ["install", "--list"])
+                .stdout("suitcase v0.1.3:\n    suitcase\n"),
+        );
+
+        upgrade(InternalCommandOptions {
+            shell: &shell,
+            base_args: &BaseArgs {
+                dry_run: true,
+                ..base_args()
+            },
+            config: &Config::default(),
+            options: &UpgradeOptions { offline: false },
+        })
+        .unwrap();
+        shell.assert_all_used();
+    }
+
+    #[test]
+    fn fails_in_offline_mode_when_installed_from_crates_io() {
+        let shell = ScriptedShell::new().expect(
+            Invocation::new("cargo", ["install", "--list"])
+                .stdout("suitcase v0.1.3:\n    suitcase\n"),
+        );
+
+        assert!(run(&shell, true).is_err());
+        shell.assert_all_used();
+    }
+
+    #[test]
+    fn fails_when_not_installed_with_cargo() {
+        let shell = ScriptedShell::new().expect(
+            Invocation::new("cargo", ["install", "--list"]).stdout("ripgrep v13.0.0:\n    rg\n"),
+        );
+
+        assert!(run(&shell, false).is_err());
+    }
+}
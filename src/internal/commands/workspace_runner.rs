@@ -0,0 +1,672 @@
+use crate::{
+    internal::{
+        cli::BaseArgs,
+        config::Config,
+        junit::write_junit_report,
+        report::{ProjectRecord, Record, Reporter, SummaryRecord},
+        run_summary::RunSummaryOptions,
+        shell::{
+            catch_interrupts, is_interrupted, DryRunShell, OutputLine, Shell, ShellCommand,
+            ShellError, ShellOutput,
+        },
+        utils::{
+            dart::{DartProjectMetadata, DartShell},
+            dependency_graph::DependencyGraph,
+            parallel::run_in_parallel,
+            project_filter::ProjectFilterOptions,
+            retry::RetryOptions,
+        },
+    },
+    progress,
+};
+use anyhow::Context;
+use clap::Args;
+use colored::Colorize;
+use log::{info, warn};
+use std::{
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use super::{DiscoveryError, ProjectFailure};
+
+/// Options for finding the projects a command works on, shared by every command that works on
+/// multiple projects.
+#[derive(Args, Debug, Default)]
+pub struct ProjectDiscoveryOptions {
+    /// The path from which to search for Dart projects.
+    #[arg(default_value = ".", short, long)]
+    pub path: PathBuf,
+
+    /// The maximum directory depth (relative to the search path) at which to look for Dart projects.
+    #[arg(long)]
+    pub max_depth: Option<usize>,
+
+    /// Filters for selecting which of the found projects to run on.
+    #[command(flatten)]
+    pub filter: ProjectFilterOptions,
+}
+
+/// Options shared by every command that runs in multiple projects.
+#[derive(Args, Debug, Default)]
+pub struct WorkspaceOptions {
+    /// Options for finding the projects to run in.
+    #[command(flatten)]
+    pub discovery: ProjectDiscoveryOptions,
+
+    /// Exit the process immediately if any of the commands run on the Dart projects fail.
+    #[arg(default_value = "false", short, long)]
+    pub fail_fast: bool,
+
+    /// Keep running the command on the remaining Dart projects if any of them fail (overrides
+    /// the configured default).
+    #[arg(default_value = "false", long, overrides_with = "fail_fast")]
+    pub no_fail_fast: bool,
+
+    /// Show the output of the command run on each Dart project.
+    #[arg(default_value = "false", short, long)]
+    pub show_output: bool,
+
+    /// Hide the output of the command run on each Dart project (overrides the configured
+    /// default).
+    #[arg(default_value = "false", long, overrides_with = "show_output")]
+    pub no_show_output: bool,
+
+    /// Kill the command in a project (and every process it started) when it runs longer than the
+    /// given duration (like `90s` or `10m`).
+    #[arg(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    pub timeout: Option<Duration>,
+
+    /// Options for retrying the command in projects where it fails.
+    #[command(flatten)]
+    pub retry: RetryOptions,
+
+    /// Options for the summary of failed projects and their logs.
+    #[command(flatten)]
+    pub summary: RunSummaryOptions,
+}
+
+impl ProjectDiscoveryOptions {
+    /// Finds the projects of the given kind in the configured path and applies the configured
+    /// filters. Errors are marked with `DiscoveryError`.
+    pub fn find_projects(
+        &self,
+        shell: &dyn Shell,
+        config: &Config,
+        kind: ProjectKind,
+    ) -> anyhow::Result<Vec<DartProjectMetadata>> {
+        self.find_projects_unmarked(shell, config, kind)
+            .context(DiscoveryError)
+    }
+
+    fn find_projects_unmarked(
+        &self,
+        shell: &dyn Shell,
+        config: &Config,
+        kind: ProjectKind,
+    ) -> anyhow::Result<Vec<DartProjectMetadata>> {
+        let path = &self.path;
+        let filter = self.filter.with_config_defaults(config);
+        let dart_shell = DartShell::new(config.ignored_folders());
+
+        let mut projects = progress!("Finding Dart projects", {
+            dart_shell
+                .find_dart_projects(Some(path), self.max_depth)
+                .context(format!(
+                    "trying to find Dart projects in path '{}'",
+                    path.display()
+                ))
+        })?;
+
+        info!("Found {} Dart and Flutter projects", projects.len());
+
+        // The filters are applied to all projects before selecting the given kind, so
+        // `--depends-on` also finds projects that depend on a package through projects of
+        // another kind.
+        if filter.is_active() {
+            let root = path
+                .canonicalize()
+                .context(format!("trying to resolve path '{}'", path.display()))?;
+            projects = filter
+                .apply(shell, &root, projects)
+                .context("trying to filter projects")?;
+            info!("Selected {} projects matching the filters", projects.len());
+        }
+
+        match kind {
+            ProjectKind::All => {}
+            ProjectKind::DartOnly => {
+                projects.retain(|project| !project.is_flutter_project);
+                info!("Selected {} Dart (non-Flutter) projects", projects.len());
+            }
+            ProjectKind::FlutterOnly => {
+                projects.retain(|project| project.is_flutter_project);
+                info!("Selected {} Flutter projects", projects.len());
+            }
+        }
+
+        if projects.is_empty() {
+            info!("No projects found");
+        }
+
+        Ok(projects)
+    }
+}
+
+/// The kinds of projects a command runs in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectKind {
+    /// Both Dart and Flutter projects.
+    All,
+    /// Dart projects that are not Flutter projects.
+    DartOnly,
+    /// Flutter projects.
+    FlutterOnly,
+}
+
+/// Runs a command in every project of a workspace, for commands like `ford` and `fua`.
+///
+/// The runner takes care of finding and filtering the projects, running an action in each of
+/// them (sequentially, in parallel or in dependency order), retries, fail-fast, Ctrl-C, showing
+/// and capturing output, and reporting the results (as records, a JUnit report, log files and a
+/// summary of the failed projects).
+///
+/// Commands are run with the directory of the project as their working directory, so the working
+/// directory of this process is never changed.
+pub struct WorkspaceRunner<'a> {
+    name: &'a str,
+    shell: &'a dyn Shell,
+    dry_run_shell: Option<DryRunShell>,
+    base_args: &'a BaseArgs,
+    config: &'a Config,
+    options: &'a WorkspaceOptions,
+    fail_fast: bool,
+    show_output: bool,
+    jobs: NonZeroUsize,
+    ordered: bool,
+    junit: Option<&'a Path>,
+    reporter: Reporter,
+    /// The records of the projects that were skipped before `run` (see `report_skipped`).
+    skipped: Mutex<Vec<ProjectRecord>>,
+    started_at: Instant,
+}
+
+/// A project that an action of `WorkspaceRunner::run` is run for.
+pub struct ProjectRun<'a> {
+    pub project: &'a DartProjectMetadata,
+    shell: &'a dyn Shell,
+    timeout: Option<Duration>,
+    show_output: bool,
+    strip_ansi: bool,
+}
+
+impl ProjectRun<'_> {
+    /// Runs the given command in the directory of the project, with the configured timeout and
+    /// while showing its output if requested. In dry-run mode, the command is only printed.
+    pub fn run_command(&self, command: ShellCommand) -> Result<ShellOutput, ShellError> {
+        command
+            .current_dir(&self.project.path)
+            .timeout(self.timeout)
+            .run_streaming(self.shell, &mut |line| {
+                if self.show_output {
+                    log_project_output_line(self.project, line, self.strip_ansi)
+                }
+            })
+    }
+}
+
+impl<'a> WorkspaceRunner<'a> {
+    /// Creates a runner for the command with the given name (like `ford`), which runs projects
+    /// one at a time, in no particular order and without writing a JUnit report.
+    pub fn new(
+        name: &'a str,
+        shell: &'a dyn Shell,
+        base_args: &'a BaseArgs,
+        config: &'a Config,
+        options: &'a WorkspaceOptions,
+    ) -> Self {
+        Self {
+            name,
+            shell,
+            dry_run_shell: base_args
+                .dry_run
+                .then(|| DryRunShell::new(base_args.output)),
+            base_args,
+            config,
+            options,
+            fail_fast: Config::resolve_flag(
+                options.fail_fast,
+                options.no_fail_fast,
+                &config.fail_fast,
+            ),
+            show_output: Config::resolve_flag(
+                options.show_output,
+                options.no_show_output,
+                &config.show_output,
+            ),
+            jobs: NonZeroUsize::MIN,
+            ordered: false,
+            junit: None,
+            reporter: Reporter::new(base_args.output),
+            skipped: Mutex::new(vec![]),
+            started_at: Instant::now(),
+        }
+    }
+
+    /// Sets the maximum number of projects to run in concurrently.
+    pub fn jobs(mut self, jobs: NonZeroUsize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// Runs projects in dependency order, so every project runs after the local projects it
+    /// depends on.
+    pub fn ordered(mut self, ordered: bool) -> Self {
+        self.ordered = ordered;
+        self
+    }
+
+    /// Writes a JUnit XML report with one test case per project to the given file.
+    pub fn junit(mut self, path: Option<&'a Path>) -> Self {
+        self.junit = path;
+        self
+    }
+
+    /// The shell to run commands that change something on, which only prints the commands in
+    /// dry-run mode.
+    pub fn mutating_shell(&self) -> &dyn Shell {
+        match &self.dry_run_shell {
+            Some(dry_run_shell) => dry_run_shell,
+            None => self.shell,
+        }
+    }
+
+    /// Finds the projects of the given kind in the configured path and applies the configured
+    /// filters. Errors are marked with `DiscoveryError`.
+    pub fn find_projects(&self, kind: ProjectKind) -> anyhow::Result<Vec<DartProjectMetadata>> {
+        self.options
+            .discovery
+            .find_projects(self.shell, self.config, kind)
+    }
+
+    /// Reports the given project as skipped, for projects that are left out before `run` (like
+    /// projects that are already up to date). The project is included in the summary and the
+    /// JUnit report of the next `run`.
+    pub fn report_skipped(&self, project: &DartProjectMetadata) {
+        let record = ProjectRecord::skipped(project);
+        self.reporter.report(Record::Project(record.clone()));
+        self.skipped.lock().unwrap().push(record);
+    }
+
+    /// Runs `action` for every project (retrying it if needed) and reports the results, where
+    /// `command` describes what is run (like `dart pub get`) and `activity` is shown while it runs
+    /// (like `Running command`).
+    ///
+    /// Returns the projects in which the action failed, or an error when the results could not be
+    /// reported.
+    pub fn run(
+        &self,
+        command: &str,
+        activity: &str,
+        projects: &[DartProjectMetadata],
+        action: impl Fn(&ProjectRun) -> Result<ShellOutput, ShellError> + Sync,
+    ) -> anyhow::Result<Vec<ProjectFailure>> {
+        let retry = &self.options.retry;
+        let mut errors: Vec<(&DartProjectMetadata, u32, ShellError)> = vec![];
+        let mut succeeded = 0;
+        let skipped = std::mem::take(&mut *self.skipped.lock().unwrap());
+        let total = projects.len() + skipped.len();
+        let project_records = Mutex::new(skipped);
+        let report_project = |record: ProjectRecord| {
+            self.reporter.report(Record::Project(record.clone()));
+            project_records.lock().unwrap().push(record);
+        };
+
+        // Runs the action in the given project (retrying it if needed). When a prompt is given,
+        // every attempt is shown using the prompt.
+        let run_project = |project: &DartProjectMetadata, prompt: Option<&str>| {
+            let project_started_at = Instant::now();
+            let project_run = ProjectRun {
+                project,
+                shell: self.mutating_shell(),
+                timeout: self.options.timeout,
+                show_output: self.show_output,
+                strip_ansi: self.base_args.strip_ansi,
+            };
+            let description = format!("'{}' in '{}'", command, project.name);
+            let (result, attempts) = retry.run(&description, |attempt| {
+                let Some(prompt) = prompt else {
+                    return action(&project_run);
+                };
+
+                let prompt = format!("{}{}", prompt, retry.describe_attempt(attempt));
+                if self.show_output {
+                    info!("{}...", prompt);
+                    let result = action(&project_run);
+                    log_project_result(project, &result);
+                    result
+                } else {
+                    progress!(prompt.as_str(), action(&project_run))
+                }
+            });
+
+            report_project(ProjectRecord::new(
+                project,
+                &result,
+                attempts,
+                project_started_at.elapsed(),
+            ));
+            (result, attempts)
+        };
+
+        let batches: Vec<Vec<&DartProjectMetadata>> = if self.ordered {
+            let levels = DependencyGraph::new(projects)
+                .levels()
+                .context("trying to determine the dependency order of the projects")?;
+            info!("{} in dependency order ({} levels)", activity, levels.len());
+
+            levels
+                .into_iter()
+                .map(|level| level.into_iter().map(|index| &projects[index]).collect())
+                .collect()
+        } else {
+            vec![projects.iter().collect()]
+        };
+
+        // Ctrl-C now stops the running commands, so the results so far can still be reported.
+        let interrupt_guard = catch_interrupts();
+
+        if self.jobs.get() == 1 {
+            for project in batches.into_iter().flatten() {
+                if (self.fail_fast && !errors.is_empty()) || is_interrupted() {
+                    info!("{} '{}' (skipped)", "-".dimmed(), project.name);
+                    report_project(ProjectRecord::skipped(project));
+                    continue;
+                }
+
+                let prompt = format!(
+                    "{} in '{}' ('{}')",
+                    activity,
+                    project.name,
+                    project.path.display()
+                );
+
+                match run_project(project, Some(&prompt)) {
+                    (Ok(_), _) => succeeded += 1,
+                    (Err(error), attempts) => errors.push((project, attempts, error)),
+                }
+            }
+        } else {
+            let batch_count = batches.len();
+            for (batch_index, batch) in batches.into_iter().enumerate() {
+                if batch.is_empty() {
+                    continue;
+                }
+                if (self.fail_fast && !errors.is_empty()) || is_interrupted() {
+                    for project in batch {
+                        info!("{} '{}' (skipped)", "-".dimmed(), project.name);
+                        report_project(ProjectRecord::skipped(project));
+                    }
+                    continue;
+                }
+
+                let prompt = if batch_count > 1 {
+                    format!(
+                        "{} in {} projects of level {}/{} ({} at a time)",
+                        activity,
+                        batch.len(),
+                        batch_index + 1,
+                        batch_count,
+                        self.jobs
+                    )
+                } else {
+                    format!(
+                        "{} in {} projects ({} at a time)",
+                        activity,
+                        batch.len(),
+                        self.jobs
+                    )
+                };
+                let run_batch = || {
+                    run_in_parallel(
+                        &batch,
+                        self.jobs,
+                        |project| run_project(project, None),
+                        |(result, _)| (self.fail_fast && result.is_err()) || is_interrupted(),
+                    )
+                };
+
+                let results = if self.show_output {
+                    info!("{}...", prompt);
+                    run_batch()
+                } else {
+                    progress!(prompt.as_str(), {
+                        let results = run_batch();
+                        if results.iter().flatten().any(|(result, _)| result.is_err()) {
+                            Err(results)
+                        } else {
+                            Ok(results)
+                        }
+                    })
+                    .unwrap_or_else(|results| results)
+                };
+
+                for (project, result) in batch.into_iter().zip(results) {
+                    let Some((result, attempts)) = result else {
+                        info!("{} '{}' (skipped)", "-".dimmed(), project.name);
+                        report_project(ProjectRecord::skipped(project));
+                        continue;
+                    };
+
+                    log_project_result(project, &result);
+
+                    match result {
+                        Ok(_) => succeeded += 1,
+                        Err(error) => errors.push((project, attempts, error)),
+                    }
+                }
+            }
+        }
+
+        drop(interrupt_guard);
+        if is_interrupted() {
+            warn!("Interrupted, projects that had not started yet were skipped");
+        }
+
+        self.reporter.report(Record::Summary(SummaryRecord::new(
+            command.to_string(),
+            total,
+            succeeded,
+            errors.iter().map(|(_, _, error)| error),
+            self.started_at.elapsed(),
+        )));
+
+        let mut project_records = project_records.into_inner().unwrap();
+        // Parallel runs finish in any order, so sort the records to keep reports stable.
+        project_records.sort_by(|a, b| a.path.cmp(&b.path));
+
+        if let Some(junit_path) = self.junit {
+            write_junit_report(
+                junit_path,
+                &format!("{}: {}", self.name, command),
+                &project_records,
+                self.started_at.elapsed(),
+            )?;
+            info!("Wrote JUnit report to '{}'", junit_path.display());
+        }
+
+        self.options.summary.report(&project_records)?;
+
+        Ok(errors
+            .into_iter()
+            .map(|(project, attempts, error)| ProjectFailure {
+                project: project.name.clone(),
+                attempts,
+                error,
+            })
+            .collect())
+    }
+}
+
+/// Logs a single line of live output of a command that is running in the given project,
+/// prefixed with the name of the project (and without ANSI escape sequences if `strip_ansi` is
+/// set).
+fn log_project_output_line(project: &DartProjectMetadata, line: OutputLine, strip_ansi: bool) {
+    let prefix = format!("[{}]", project.name);
+    let line = if strip_ansi {
+        line.without_ansi()
+    } else {
+        line
+    };
+    match line {
+        OutputLine::Stdout(line) => info!("{} {}", prefix.cyan(), line),
+        OutputLine::Stderr(line) => info!("{} {}", prefix.yellow(), line),
+    }
+}
+
+/// Logs whether a command that was run in the given project succeeded or failed.
+fn log_project_result(project: &DartProjectMetadata, result: &Result<ShellOutput, ShellError>) {
+    info!(
+        "{} '{}' ('{}')",
+        if result.is_ok() {
+            "✔".green()
+        } else {
+            "✘".red()
+        },
+        project.name,
+        project.path.display()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::{
+        commands::test_utils::{
+            base_args, create_dart_project, create_project_with_pubspec, workspace_options,
+        },
+        shell::{Invocation, ScriptedShell},
+    };
+    use std::env;
+
+    #[test]
+    fn includes_projects_skipped_before_running_in_the_junit_report() {
+        let root = tempfile::tempdir().unwrap();
+        let app = create_dart_project(root.path(), "app", "app", true);
+        create_dart_project(root.path(), "web", "web", true);
+        let junit = root.path().join("junit.xml");
+
+        let shell = ScriptedShell::new().expect(Invocation::new("fvm", ["use"]).in_dir(&app));
+        let base_args = base_args();
+        let config = Config::default();
+        let options = workspace_options(root.path());
+        let runner =
+            WorkspaceRunner::new("test", &shell, &base_args, &config, &options).junit(Some(&junit));
+
+        let (up_to_date, projects): (Vec<_>, Vec<_>) = runner
+            .find_projects(ProjectKind::All)
+            .unwrap()
+            .into_iter()
+            .partition(|project| project.name == "web");
+        runner.report_skipped(&up_to_date[0]);
+        runner
+            .run("fvm use", "Setting FVM version", &projects, |run| {
+                run.run_command(ShellCommand::new("fvm").arg("use"))
+            })
+            .unwrap();
+
+        let report = std::fs::read_to_string(&junit).unwrap();
+        assert!(report.contains(r#"tests="2" failures="0" errors="0" skipped="1""#));
+        assert!(report.contains(r#"<testcase name="web""#));
+        shell.assert_all_used();
+    }
+
+    #[test]
+    fn finds_dependents_through_projects_of_another_kind() {
+        let root = tempfile::tempdir().unwrap();
+        let flutter = "  flutter:\n    sdk: flutter\n";
+        create_project_with_pubspec(
+            root.path(),
+            "app",
+            &format!(
+                "name: app\ndependencies:\n{}  auth:\n    path: ../auth\n",
+                flutter
+            ),
+        );
+        create_project_with_pubspec(
+            root.path(),
+            "auth",
+            "name: auth\ndependencies:\n  core:\n    path: ../core\n",
+        );
+        create_project_with_pubspec(
+            root.path(),
+            "cli",
+            "name: cli\ndependencies:\n  ui:\n    path: ../ui\n",
+        );
+        create_project_with_pubspec(
+            root.path(),
+            "ui",
+            &format!(
+                "name: ui\ndependencies:\n{}  core:\n    path: ../core\n",
+                flutter
+            ),
+        );
+        create_dart_project(root.path(), "core", "core", false);
+        create_dart_project(root.path(), "web", "web", true);
+
+        let mut options = workspace_options(root.path()).discovery;
+        options.filter.depends_on = vec!["core".to_string()];
+        let find = |kind| {
+            let mut names: Vec<String> = options
+                .find_projects(&ScriptedShell::new(), &Config::default(), kind)
+                .unwrap()
+                .into_iter()
+                .map(|project| project.name)
+                .collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(find(ProjectKind::FlutterOnly), ["app", "ui"]);
+        assert_eq!(find(ProjectKind::DartOnly), ["auth", "cli"]);
+    }
+
+    #[test]
+    fn runs_action_in_projects_of_the_given_kind_without_changing_cwd() {
+        let root = tempfile::tempdir().unwrap();
+        let app = create_dart_project(root.path(), "app", "app", true);
+        let ui = create_dart_project(root.path(), "packages/ui", "ui", true);
+        create_dart_project(root.path(), "packages/core", "core", false);
+
+        let shell = ScriptedShell::new()
+            .expect(Invocation::new("flutter", ["test"]).in_dir(&app))
+            .expect(
+                Invocation::new("flutter", ["test"])
+                    .in_dir(&ui)
+                    .stderr("Some tests failed.\n")
+                    .exit_code(1),
+            );
+
+        let cwd = env::current_dir().unwrap();
+        let base_args = base_args();
+        let config = Config::default();
+        let options = workspace_options(root.path());
+        let runner = WorkspaceRunner::new("test", &shell, &base_args, &config, &options);
+
+        let projects = runner.find_projects(ProjectKind::FlutterOnly).unwrap();
+        let failures = runner
+            .run("flutter test", "Testing", &projects, |run| {
+                run.run_command(ShellCommand::new("flutter").arg("test"))
+            })
+            .unwrap();
+
+        assert!(matches!(
+            failures.as_slice(),
+            [ProjectFailure { project, attempts: 1, .. }] if project == "ui"
+        ));
+        assert_eq!(env::current_dir().unwrap(), cwd);
+        shell.assert_all_used();
+    }
+}
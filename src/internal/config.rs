@@ -0,0 +1,402 @@
+use super::utils::dart::IGNORED_FOLDERS;
+use anyhow::Context;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    env, fmt,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
+
+/// The name of the configuration file, both for repositories and for the user-level config.
+pub const CONFIG_FILE_NAME: &str = "suitcase.yaml";
+
+/// The contents of a single `suitcase.yaml` configuration file.
+///
+/// Every field is optional, so a configuration file only needs to specify the values it wants to
+/// change.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    /// Folders (in addition to the built-in ones) that are never searched for Dart projects.
+    pub ignored_folders: Vec<String>,
+
+    /// Default values for flags of commands that run across projects (`ford` and `fua`).
+    pub defaults: ConfigFileDefaults,
+
+    /// Default project filters for commands that run across projects (`ford` and `fua`).
+    pub projects: ConfigFileProjects,
+
+    /// Named aliases for commands run by `ford` (e.g. `get: flutter pub get`).
+    pub aliases: BTreeMap<String, String>,
+
+    /// The command used by `gho` to open URLs (e.g. `firefox --new-tab %s`). The URL replaces
+    /// `%s`, or is appended if the command does not contain `%s`.
+    pub browser: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFileDefaults {
+    pub fail_fast: Option<bool>,
+    pub show_output: Option<bool>,
+    pub jobs: Option<NonZeroUsize>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFileProjects {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+/// Where a configuration value came from.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", content = "path", rename_all = "snake_case")]
+pub enum ConfigSource {
+    /// The built-in default value.
+    Default,
+    /// The user-level configuration file.
+    User(PathBuf),
+    /// The configuration file of the current repository (or one of its parent directories).
+    Repository(PathBuf),
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::User(path) => write!(f, "user config at '{}'", path.display()),
+            ConfigSource::Repository(path) => {
+                write!(f, "repository config at '{}'", path.display())
+            }
+        }
+    }
+}
+
+/// A configuration value along with where it came from.
+#[derive(Serialize, Debug, Clone)]
+pub struct Sourced<T> {
+    pub value: T,
+    pub source: ConfigSource,
+}
+
+impl<T> Sourced<T> {
+    fn new(value: T, source: &ConfigSource) -> Self {
+        Self {
+            value,
+            source: source.clone(),
+        }
+    }
+}
+
+/// The effective configuration, merged from the built-in defaults, the user-level configuration
+/// file and the repository configuration file (in increasing order of precedence).
+///
+/// Lists are combined across all files, while single values and aliases from files with a higher
+/// precedence replace those from files with a lower precedence.
+#[derive(Serialize, Debug, Clone)]
+pub struct Config {
+    pub user_config_path: Option<PathBuf>,
+    pub repository_config_path: Option<PathBuf>,
+    pub ignored_folders: Vec<Sourced<String>>,
+    pub fail_fast: Sourced<bool>,
+    pub show_output: Sourced<bool>,
+    pub jobs: Sourced<NonZeroUsize>,
+    pub include: Vec<Sourced<String>>,
+    pub exclude: Vec<Sourced<String>>,
+    pub aliases: BTreeMap<String, Sourced<String>>,
+    pub browser: Option<Sourced<String>>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            user_config_path: None,
+            repository_config_path: None,
+            ignored_folders: IGNORED_FOLDERS
+                .iter()
+                .map(|folder| Sourced::new(folder.to_string(), &ConfigSource::Default))
+                .collect(),
+            fail_fast: Sourced::new(false, &ConfigSource::Default),
+            show_output: Sourced::new(false, &ConfigSource::Default),
+            jobs: Sourced::new(NonZeroUsize::MIN, &ConfigSource::Default),
+            include: vec![],
+            exclude: vec![],
+            aliases: BTreeMap::new(),
+            browser: None,
+        }
+    }
+}
+
+impl Config {
+    /// Loads the effective configuration for the given working directory.
+    ///
+    /// The repository configuration file is found by walking up from `cwd` until a directory
+    /// containing a `suitcase.yaml` file is found. The user-level configuration file is read from
+    /// `$XDG_CONFIG_HOME/suitcase/suitcase.yaml` (or `~/.config/suitcase/suitcase.yaml`).
+    pub fn load(cwd: &Path) -> anyhow::Result<Self> {
+        Config::load_from(Config::user_config_path(), cwd)
+    }
+
+    /// Loads the effective configuration for the given working directory, using the given path
+    /// for the user-level configuration file.
+    fn load_from(user_config_path: Option<PathBuf>, cwd: &Path) -> anyhow::Result<Self> {
+        let mut config = Config::default();
+
+        if let Some(path) = user_config_path.filter(|path| path.is_file()) {
+            let file = ConfigFile::read(&path)?;
+            config.merge(file, ConfigSource::User(path.clone()));
+            config.user_config_path = Some(path);
+        }
+
+        if let Some(path) = Config::find_repository_config_path(cwd) {
+            let file = ConfigFile::read(&path)?;
+            config.merge(file, ConfigSource::Repository(path.clone()));
+            config.repository_config_path = Some(path);
+        }
+
+        Ok(config)
+    }
+
+    /// Returns the path of the user-level configuration file (which may not exist).
+    pub fn user_config_path() -> Option<PathBuf> {
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+        Some(config_home.join("suitcase").join(CONFIG_FILE_NAME))
+    }
+
+    /// Returns the path of the closest `suitcase.yaml` file in `cwd` or any of its parents.
+    pub fn find_repository_config_path(cwd: &Path) -> Option<PathBuf> {
+        cwd.ancestors()
+            .map(|directory| directory.join(CONFIG_FILE_NAME))
+            .find(|path| path.is_file())
+    }
+
+    /// Returns the names of all folders that are never searched for Dart projects.
+    pub fn ignored_folders(&self) -> Vec<String> {
+        self.ignored_folders
+            .iter()
+            .map(|folder| folder.value.clone())
+            .collect()
+    }
+
+    /// Resolves the value of a boolean flag that can be enabled (`--flag`) or disabled
+    /// (`--no-flag`) on the command line, falling back to the configured value.
+    pub fn resolve_flag(enabled: bool, disabled: bool, configured: &Sourced<bool>) -> bool {
+        match (enabled, disabled) {
+            (true, _) => true,
+            (_, true) => false,
+            _ => configured.value,
+        }
+    }
+
+    /// Expands the given command if its first word is a configured alias.
+    pub fn expand_alias(&self, command: &[String]) -> Vec<String> {
+        let Some((first, rest)) = command.split_first() else {
+            return vec![];
+        };
+
+        match self.aliases.get(first) {
+            Some(alias) => {
+                debug!("expanding alias '{}' to '{}'", first, alias.value);
+                std::iter::once(alias.value.clone())
+                    .chain(rest.iter().cloned())
+                    .collect()
+            }
+            None => command.to_vec(),
+        }
+    }
+
+    fn merge(&mut self, file: ConfigFile, source: ConfigSource) {
+        let sourced = |values: Vec<String>| {
+            values
+                .into_iter()
+                .map(|value| Sourced::new(value, &source))
+                .collect::<Vec<_>>()
+        };
+
+        self.ignored_folders.extend(sourced(file.ignored_folders));
+        self.include.extend(sourced(file.projects.include));
+        self.exclude.extend(sourced(file.projects.exclude));
+
+        if let Some(fail_fast) = file.defaults.fail_fast {
+            self.fail_fast = Sourced::new(fail_fast, &source);
+        }
+        if let Some(show_output) = file.defaults.show_output {
+            self.show_output = Sourced::new(show_output, &source);
+        }
+        if let Some(jobs) = file.defaults.jobs {
+            self.jobs = Sourced::new(jobs, &source);
+        }
+        if let Some(browser) = file.browser {
+            self.browser = Some(Sourced::new(browser, &source));
+        }
+
+        for (name, command) in file.aliases {
+            self.aliases.insert(name, Sourced::new(command, &source));
+        }
+    }
+}
+
+impl ConfigFile {
+    /// Reads and parses the configuration file at the given path.
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        debug!("reading config file at {:?}", path);
+
+        let file = std::fs::File::open(path).context(format!(
+            "trying to open config file at path '{}'",
+            path.display()
+        ))?;
+
+        serde_yaml::from_reader::<_, Option<ConfigFile>>(file)
+            .map(Option::unwrap_or_default)
+            .context(format!(
+                "trying to parse config file at path '{}'",
+                path.display()
+            ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn write(path: &Path, contents: &str) -> PathBuf {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+        path.to_path_buf()
+    }
+
+    fn values(values: &[Sourced<String>]) -> Vec<(&str, &ConfigSource)> {
+        values
+            .iter()
+            .map(|value| (value.value.as_str(), &value.source))
+            .collect()
+    }
+
+    #[test]
+    fn merges_user_and_repository_config_files() {
+        let root = tempfile::tempdir().unwrap();
+        let user = write(
+            &root.path().join("home/suitcase.yaml"),
+            "ignored_folders: [vendor]\n\
+             defaults:\n  fail_fast: true\n  jobs: 4\n\
+             projects:\n  exclude: [example]\n\
+             aliases:\n  get: flutter pub get\n  test: dart test\n\
+             browser: firefox\n",
+        );
+        let repository = write(
+            &root.path().join("repo/suitcase.yaml"),
+            "defaults:\n  jobs: 2\n\
+             projects:\n  exclude: [tools]\n\
+             aliases:\n  test: flutter test\n",
+        );
+        let cwd = root.path().join("repo/packages/core");
+        fs::create_dir_all(&cwd).unwrap();
+
+        let config = Config::load_from(Some(user.clone()), &cwd).unwrap();
+        let (user, repository) = (
+            ConfigSource::User(user.clone()),
+            ConfigSource::Repository(repository),
+        );
+
+        assert_eq!(
+            config.user_config_path,
+            Some(root.path().join("home/suitcase.yaml"))
+        );
+        assert_eq!(
+            config.repository_config_path,
+            Some(root.path().join("repo/suitcase.yaml"))
+        );
+        assert_eq!(
+            values(&config.ignored_folders).last(),
+            Some(&("vendor", &user))
+        );
+        assert_eq!(
+            values(&config.exclude),
+            [("example", &user), ("tools", &repository)]
+        );
+        assert_eq!(
+            (config.fail_fast.value, &config.fail_fast.source),
+            (true, &user)
+        );
+        assert_eq!(
+            (config.show_output.value, &config.show_output.source),
+            (false, &ConfigSource::Default)
+        );
+        assert_eq!(
+            (config.jobs.value.get(), &config.jobs.source),
+            (2, &repository)
+        );
+        assert_eq!(config.aliases["get"].value, "flutter pub get");
+        assert_eq!(
+            (
+                config.aliases["test"].value.as_str(),
+                &config.aliases["test"].source
+            ),
+            ("flutter test", &repository)
+        );
+        assert_eq!(
+            config.browser.map(|browser| browser.value).as_deref(),
+            Some("firefox")
+        );
+    }
+
+    #[test]
+    fn loads_defaults_without_or_with_empty_config_files() {
+        let root = tempfile::tempdir().unwrap();
+        let user = root.path().join("home/suitcase.yaml");
+
+        let config = Config::load_from(Some(user.clone()), root.path()).unwrap();
+        assert_eq!(config.user_config_path, None);
+        assert_eq!(config.ignored_folders().len(), IGNORED_FOLDERS.len());
+
+        write(&user, "");
+        let config = Config::load_from(Some(user.clone()), root.path()).unwrap();
+        assert_eq!(config.user_config_path, Some(user));
+        assert_eq!(config.jobs.value, NonZeroUsize::MIN);
+    }
+
+    #[test]
+    fn fails_to_load_malformed_config_files() {
+        let root = tempfile::tempdir().unwrap();
+        write(
+            &root.path().join(CONFIG_FILE_NAME),
+            "defaults:\n  fail_fats: true\n",
+        );
+
+        let error = Config::load_from(None, root.path()).unwrap_err();
+        assert!(format!("{:#}", error).contains("unknown field `fail_fats`"));
+    }
+
+    #[test]
+    fn expands_only_the_first_word_of_a_command() {
+        let mut config = Config::default();
+        config.aliases.insert(
+            "get".to_string(),
+            Sourced::new("flutter pub get".to_string(), &ConfigSource::Default),
+        );
+        let command = |words: &[&str]| {
+            words
+                .iter()
+                .map(|word| word.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            config.expand_alias(&command(&["get", "--offline"])),
+            command(&["flutter pub get", "--offline"])
+        );
+        assert_eq!(
+            config.expand_alias(&command(&["dart", "get"])),
+            command(&["dart", "get"])
+        );
+        assert!(config.expand_alias(&[]).is_empty());
+    }
+}
@@ -1,2 +1,6 @@
 pub const PACKAGE_NAME: &str = env!("CARGO_PKG_NAME");
 pub const PACKAGE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// When set, every command run by suitcase is recorded to the fixture file at this path (for use
+/// with `ScriptedShell::from_fixture` in tests).
+pub const RECORD_SHELL_ENV_VAR: &str = "SUITCASE_RECORD_SHELL";
@@ -0,0 +1,217 @@
+use super::report::{ProjectRecord, ProjectStatus};
+use anyhow::Context;
+use std::{path::Path, time::Duration};
+
+/// Writes a JUnit XML report with one `<testcase>` per project to the given file.
+///
+/// Projects that failed to run are reported as failures (when the command exited with a non-zero
+/// status) or errors (when the command could not be started, timed out or was interrupted),
+/// projects that were never run are reported as skipped.
+pub fn write_junit_report(
+    path: &Path,
+    suite_name: &str,
+    records: &[ProjectRecord],
+    duration: Duration,
+) -> anyhow::Result<()> {
+    std::fs::write(path, render_junit_report(suite_name, records, duration)).context(format!(
+        "trying to write JUnit report to '{}'",
+        path.display()
+    ))
+}
+
+/// Renders a JUnit XML report with one `<testcase>` per project.
+pub fn render_junit_report(
+    suite_name: &str,
+    records: &[ProjectRecord],
+    duration: Duration,
+) -> String {
+    let count = |status| {
+        records
+            .iter()
+            .filter(|record| record.status == status)
+            .count()
+    };
+    let errors = records
+        .iter()
+        .filter(|record| record.error.is_some())
+        .count();
+    let failures = count(ProjectStatus::Failed) - errors;
+
+    let mut xml = vec![
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#.to_string(),
+        format!(
+            r#"<testsuites name="{}" tests="{}" failures="{}" errors="{}" skipped="{}" time="{:.3}">"#,
+            escape(suite_name),
+            records.len(),
+            failures,
+            errors,
+            count(ProjectStatus::Skipped),
+            duration.as_secs_f64()
+        ),
+        format!(
+            r#"  <testsuite name="{}" tests="{}" failures="{}" errors="{}" skipped="{}" time="{:.3}" timestamp="{}">"#,
+            escape(suite_name),
+            records.len(),
+            failures,
+            errors,
+            count(ProjectStatus::Skipped),
+            duration.as_secs_f64(),
+            chrono::Local::now().format("%Y-%m-%dT%H:%M:%S")
+        ),
+    ];
+
+    for record in records {
+        xml.push(format!(
+            r#"    <testcase name="{}" classname="{}" file="{}" time="{:.3}">"#,
+            escape(&record.name),
+            escape(suite_name),
+            escape(&record.path.display().to_string()),
+            record.duration_ms as f64 / 1000.0
+        ));
+
+        match (record.status, &record.error) {
+            (ProjectStatus::Succeeded, _) => {}
+            (ProjectStatus::Skipped, _) => xml.push("      <skipped/>".to_string()),
+            (ProjectStatus::Failed, Some(error)) => {
+                xml.push(format!(r#"      <error message="{}"/>"#, escape(error)))
+            }
+            (ProjectStatus::Failed, None) => {
+                let message = match record.exit_code {
+                    Some(code) => format!("command exited with status {}", code),
+                    None => "command was terminated by a signal".to_string(),
+                };
+                xml.push(format!(
+                    r#"      <failure message="{}">{}</failure>"#,
+                    escape(&message),
+                    escape(&failure_details(record))
+                ));
+            }
+        }
+
+        if !record.stdout.is_empty() {
+            xml.push(format!(
+                "      <system-out>{}</system-out>",
+                escape(&record.stdout)
+            ));
+        }
+        if !record.stderr.is_empty() {
+            xml.push(format!(
+                "      <system-err>{}</system-err>",
+                escape(&record.stderr)
+            ));
+        }
+
+        xml.push("    </testcase>".to_string());
+    }
+
+    xml.push("  </testsuite>".to_string());
+    xml.push("</testsuites>".to_string());
+    xml.push(String::new());
+
+    xml.join("\n")
+}
+
+/// Combines the stdout and stderr of a failed command into a single failure description.
+fn failure_details(record: &ProjectRecord) -> String {
+    match (record.stdout.trim(), record.stderr.trim()) {
+        ("", "") => "(no output)".to_string(),
+        (stdout, "") => stdout.to_string(),
+        ("", stderr) => stderr.to_string(),
+        (stdout, stderr) => format!("{}\n---\n{}", stdout, stderr),
+    }
+}
+
+/// Escapes the given text for use in XML attributes and text nodes.
+///
+/// Characters that are not allowed in XML 1.0 (such as most control characters) are dropped.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn record(name: &str, status: ProjectStatus) -> ProjectRecord {
+        ProjectRecord {
+            name: name.to_string(),
+            path: PathBuf::from(format!("/workspace/{}", name)),
+            status,
+            exit_code: None,
+            attempts: 1,
+            duration_ms: 1500,
+            stdout: String::new(),
+            stderr: String::new(),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn renders_escaped_failures_errors_and_skipped_projects() {
+        let records = [
+            record("app", ProjectStatus::Succeeded),
+            ProjectRecord {
+                exit_code: Some(1),
+                stdout: "Expected: <2> & \"two\"\n".to_string(),
+                stderr: "Some tests failed.\u{1b}\n".to_string(),
+                ..record("core", ProjectStatus::Failed)
+            },
+            ProjectRecord {
+                error: Some("timed out after 30s".to_string()),
+                ..record("ui", ProjectStatus::Failed)
+            },
+            ProjectRecord {
+                attempts: 0,
+                duration_ms: 0,
+                ..record("tools", ProjectStatus::Skipped)
+            },
+        ];
+
+        let xml = render_junit_report("ford <dart test>", &records, Duration::from_secs(3));
+        let lines: Vec<&str> = xml.lines().collect();
+
+        assert_eq!(
+            lines[1],
+            r#"<testsuites name="ford &lt;dart test&gt;" tests="4" failures="1" errors="1" skipped="1" time="3.000">"#
+        );
+        assert_eq!(
+            lines[3..],
+            [
+                r#"    <testcase name="app" classname="ford &lt;dart test&gt;" file="/workspace/app" time="1.500">"#,
+                "    </testcase>",
+                r#"    <testcase name="core" classname="ford &lt;dart test&gt;" file="/workspace/core" time="1.500">"#,
+                r#"      <failure message="command exited with status 1">Expected: &lt;2&gt; &amp; &quot;two&quot;"#,
+                "---",
+                "Some tests failed.</failure>",
+                r#"      <system-out>Expected: &lt;2&gt; &amp; &quot;two&quot;"#,
+                "</system-out>",
+                "      <system-err>Some tests failed.",
+                "</system-err>",
+                "    </testcase>",
+                r#"    <testcase name="ui" classname="ford &lt;dart test&gt;" file="/workspace/ui" time="1.500">"#,
+                r#"      <error message="timed out after 30s"/>"#,
+                "    </testcase>",
+                r#"    <testcase name="tools" classname="ford &lt;dart test&gt;" file="/workspace/tools" time="0.000">"#,
+                "      <skipped/>",
+                "    </testcase>",
+                "  </testsuite>",
+                "</testsuites>",
+            ]
+        );
+    }
+}
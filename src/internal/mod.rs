@@ -1,6 +1,10 @@
-pub mod cli;
-pub mod commands;
-pub mod constants;
-pub mod progress;
-pub mod shell;
-pub mod utils;
+pub mod cli;
+pub mod commands;
+pub mod config;
+pub mod constants;
+pub mod junit;
+pub mod progress;
+pub mod report;
+pub mod run_summary;
+pub mod shell;
+pub mod utils;
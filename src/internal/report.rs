@@ -0,0 +1,273 @@
+use super::{
+    commands::deps::GraphExport,
+    config::Config,
+    shell::{ShellError, ShellOutput},
+    utils::dart::DartProjectMetadata,
+};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::{path::PathBuf, sync::Mutex, time::Duration};
+
+/// The format in which commands report their results on stdout.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable output (logged to stderr, nothing is printed to stdout).
+    #[default]
+    Text,
+    /// A single JSON array containing all records, printed when the command finishes.
+    Json,
+    /// One JSON record per line, printed as soon as the record is available.
+    Ndjson,
+}
+
+/// A single machine-readable record produced by a command.
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Record {
+    /// The result of running a command in a single project.
+    Project(ProjectRecord),
+
+    /// A summary of running a command in multiple projects.
+    Summary(SummaryRecord),
+
+    /// The URL that was opened by `gho`.
+    GitHubOpen { url: String },
+
+    /// The dependency graph between local projects, as exported by `deps graph`.
+    DependencyGraph(GraphExport),
+
+    /// The effective configuration, as shown by `config show`.
+    Config(Config),
+
+    /// The Flutter versions of a single project, as shown by `fvm status`.
+    FvmStatus(FvmStatusRecord),
+
+    /// The result of upgrading suitcase.
+    Upgrade {
+        package: String,
+        old_version: Option<String>,
+        new_version: Option<String>,
+    },
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ProjectRecord {
+    pub name: String,
+    pub path: PathBuf,
+    pub status: ProjectStatus,
+    pub exit_code: Option<i32>,
+    /// How many times the command was run (more than once when it was retried).
+    pub attempts: u32,
+    pub duration_ms: u64,
+    pub stdout: String,
+    pub stderr: String,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectStatus {
+    Succeeded,
+    Failed,
+    Skipped,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct FvmStatusRecord {
+    pub name: String,
+    pub path: PathBuf,
+    /// The Flutter version pinned by the FVM config of the project.
+    pub fvm_version: Option<String>,
+    /// The `environment.flutter` constraint of the pubspec.
+    pub flutter_constraint: Option<String>,
+    /// The `environment.sdk` constraint of the pubspec.
+    pub sdk_constraint: Option<String>,
+    /// The fields of this record that differ from the value most other projects use (only a
+    /// differing `fvm_version` makes `fvm status` fail).
+    pub drift: Vec<&'static str>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SummaryRecord {
+    pub command: String,
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    /// How many of the failed projects failed because the command timed out.
+    pub timed_out: usize,
+    /// How many of the failed projects failed because the command was interrupted by Ctrl-C.
+    pub interrupted: usize,
+    pub skipped: usize,
+    pub duration_ms: u64,
+}
+
+impl SummaryRecord {
+    /// Creates a summary of running the given command in `total` projects, of which `succeeded`
+    /// succeeded and the ones with the given errors failed. All other projects were skipped.
+    pub fn new<'a>(
+        command: String,
+        total: usize,
+        succeeded: usize,
+        errors: impl IntoIterator<Item = &'a ShellError>,
+        duration: Duration,
+    ) -> Self {
+        let (mut failed, mut timed_out, mut interrupted) = (0, 0, 0);
+        for error in errors {
+            failed += 1;
+            match error {
+                ShellError::Timeout { .. } => timed_out += 1,
+                ShellError::Interrupted { .. } => interrupted += 1,
+                _ => {}
+            }
+        }
+
+        Self {
+            command,
+            total,
+            succeeded,
+            failed,
+            timed_out,
+            interrupted,
+            skipped: total - succeeded - failed,
+            duration_ms: duration.as_millis() as u64,
+        }
+    }
+}
+
+impl ProjectRecord {
+    /// Creates a record for a command that was run in the given project (the given number of
+    /// times).
+    pub fn new(
+        project: &DartProjectMetadata,
+        result: &Result<ShellOutput, ShellError>,
+        attempts: u32,
+        duration: Duration,
+    ) -> Self {
+        let (status, exit_code, stdout, stderr, error) = match result {
+            Ok(output) => (
+                ProjectStatus::Succeeded,
+                output.status.code(),
+                output.stdout_lossy().into_owned(),
+                output.stderr_lossy().into_owned(),
+                None,
+            ),
+            Err(ShellError::HostProcessExecutionFailure {
+                status,
+                stdout,
+                stderr,
+                ..
+            }) => (
+                ProjectStatus::Failed,
+                status.code(),
+                stdout.clone(),
+                stderr.clone(),
+                None,
+            ),
+            Err(
+                error @ (ShellError::ShellStartFailure { .. }
+                | ShellError::InvalidOutputEncoding { .. }),
+            ) => (
+                ProjectStatus::Failed,
+                None,
+                String::new(),
+                String::new(),
+                Some(error.to_string()),
+            ),
+            Err(ShellError::Timeout {
+                timeout,
+                stdout,
+                stderr,
+                ..
+            }) => (
+                ProjectStatus::Failed,
+                None,
+                stdout.clone(),
+                stderr.clone(),
+                Some(format!(
+                    "timed out after {}",
+                    humantime::format_duration(*timeout)
+                )),
+            ),
+            Err(ShellError::Interrupted { stdout, stderr, .. }) => (
+                ProjectStatus::Failed,
+                None,
+                stdout.clone(),
+                stderr.clone(),
+                Some("interrupted".to_string()),
+            ),
+        };
+
+        Self {
+            name: project.name.clone(),
+            path: project.path.clone(),
+            status,
+            exit_code,
+            attempts,
+            duration_ms: duration.as_millis() as u64,
+            stdout,
+            stderr,
+            error,
+        }
+    }
+
+    /// Creates a record for a project in which the command was never run.
+    pub fn skipped(project: &DartProjectMetadata) -> Self {
+        Self {
+            name: project.name.clone(),
+            path: project.path.clone(),
+            status: ProjectStatus::Skipped,
+            exit_code: None,
+            attempts: 0,
+            duration_ms: 0,
+            stdout: String::new(),
+            stderr: String::new(),
+            error: None,
+        }
+    }
+}
+
+/// Prints machine-readable records to stdout in the configured `OutputFormat`.
+///
+/// In `Text` mode, all records are discarded. In `Json` mode, records are collected and printed
+/// as a single JSON array once the reporter is dropped, so they are also printed when a command
+/// returns early with an error.
+pub struct Reporter {
+    format: OutputFormat,
+    records: Mutex<Vec<Record>>,
+}
+
+impl Reporter {
+    /// Creates a new `Reporter` for the given output format.
+    pub fn new(format: OutputFormat) -> Self {
+        Self {
+            format,
+            records: Mutex::new(vec![]),
+        }
+    }
+
+    /// Reports the given record.
+    pub fn report(&self, record: Record) {
+        match self.format {
+            OutputFormat::Text => {}
+            OutputFormat::Json => self.records.lock().unwrap().push(record),
+            OutputFormat::Ndjson => match serde_json::to_string(&record) {
+                Ok(line) => println!("{}", line),
+                Err(err) => log::error!("failed to serialize record: {}", err),
+            },
+        }
+    }
+}
+
+impl Drop for Reporter {
+    fn drop(&mut self) {
+        if self.format != OutputFormat::Json {
+            return;
+        }
+
+        let records = self.records.get_mut().unwrap();
+        match serde_json::to_string_pretty(records) {
+            Ok(json) => println!("{}", json),
+            Err(err) => log::error!("failed to serialize records: {}", err),
+        }
+    }
+}
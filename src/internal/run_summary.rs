@@ -0,0 +1,272 @@
+use super::{
+    report::{ProjectRecord, ProjectStatus},
+    utils::table::render_table,
+};
+use anyhow::Context;
+use clap::Args;
+use log::{error, info};
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// How many lines of stderr are shown for every failed project by default.
+const DEFAULT_STDERR_LINES: usize = 10;
+
+/// Options for reporting the results of a command that was run in multiple projects.
+#[derive(Args, Debug, Clone)]
+pub struct RunSummaryOptions {
+    /// Write the full output of the command in every project to a `<PROJECT>.log` file in the
+    /// given directory.
+    #[arg(long, value_name = "DIR")]
+    pub log_dir: Option<PathBuf>,
+
+    /// The number of lines at the end of stderr to show for every failed project in the summary.
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_STDERR_LINES)]
+    pub stderr_lines: usize,
+}
+
+impl Default for RunSummaryOptions {
+    fn default() -> Self {
+        Self {
+            log_dir: None,
+            stderr_lines: DEFAULT_STDERR_LINES,
+        }
+    }
+}
+
+impl RunSummaryOptions {
+    /// Writes the log files of the given projects (if `log_dir` is set) and logs the summary of
+    /// the projects in which the command failed (if any).
+    pub fn report(&self, records: &[ProjectRecord]) -> anyhow::Result<()> {
+        if let Some(log_dir) = &self.log_dir {
+            let written = write_project_logs(log_dir, records)?;
+            info!(
+                "Wrote the logs of {} projects to '{}'",
+                written,
+                log_dir.display()
+            );
+        }
+
+        if let Some(summary) = render_failure_summary(records, self.stderr_lines) {
+            error!("{}", summary);
+        }
+
+        Ok(())
+    }
+}
+
+/// Renders a table of the projects in which the command failed (with their path, exit code,
+/// number of attempts and duration), followed by the last `stderr_lines` lines of stderr of each of them.
+///
+/// Returns `None` when the command did not fail in any project.
+pub fn render_failure_summary(records: &[ProjectRecord], stderr_lines: usize) -> Option<String> {
+    let failed: Vec<&ProjectRecord> = records
+        .iter()
+        .filter(|record| record.status == ProjectStatus::Failed)
+        .collect();
+    if failed.is_empty() {
+        return None;
+    }
+
+    let rows: Vec<[String; 5]> = failed
+        .iter()
+        .map(|record| {
+            [
+                record.name.clone(),
+                record.path.display().to_string(),
+                record
+                    .exit_code
+                    .map_or_else(|| "-".to_string(), |code| code.to_string()),
+                record.attempts.to_string(),
+                format_duration_ms(record.duration_ms),
+            ]
+        })
+        .collect();
+
+    let mut lines = vec![format!(
+        "The command failed in {} of {} projects:",
+        failed.len(),
+        records.len()
+    )];
+    lines.push(String::new());
+    lines.extend(
+        render_table(["PROJECT", "PATH", "EXIT", "ATTEMPTS", "DURATION"], &rows)
+            .into_iter()
+            .map(|line| format!("  {}", line)),
+    );
+
+    for record in failed {
+        let stderr: Vec<&str> = record.stderr.lines().collect();
+        let shown = &stderr[stderr.len().saturating_sub(stderr_lines)..];
+        if shown.is_empty() && record.error.is_none() {
+            continue;
+        }
+
+        let mut details = vec![];
+        if let Some(error) = &record.error {
+            details.push(error.clone());
+        }
+        if !shown.is_empty() {
+            details.push(if shown.len() < stderr.len() {
+                format!("last {} of {} lines of stderr", shown.len(), stderr.len())
+            } else {
+                "stderr".to_string()
+            });
+        }
+
+        lines.push(String::new());
+        lines.push(format!("{} ({}):", record.name, details.join(", ")));
+        lines.extend(shown.iter().map(|line| format!("  | {}", line)));
+    }
+
+    Some(lines.join("\n"))
+}
+
+/// Writes the full output of every project the command was run in to a `<PROJECT>.log` file in
+/// the given directory (creating it if needed), and returns the number of files written.
+///
+/// When multiple projects have the same name, a number is added to the names of the later files.
+pub fn write_project_logs(dir: &Path, records: &[ProjectRecord]) -> anyhow::Result<usize> {
+    fs::create_dir_all(dir).context(format!(
+        "trying to create log directory '{}'",
+        dir.display()
+    ))?;
+
+    let mut used_names = HashSet::new();
+    let mut written = 0;
+    for record in records {
+        if record.status == ProjectStatus::Skipped {
+            continue;
+        }
+
+        let mut file_name = format!("{}.log", record.name);
+        let mut index = 1;
+        while !used_names.insert(file_name.clone()) {
+            index += 1;
+            file_name = format!("{}-{}.log", record.name, index);
+        }
+
+        let path = dir.join(file_name);
+        fs::write(&path, render_project_log(record))
+            .context(format!("trying to write log file '{}'", path.display()))?;
+        written += 1;
+    }
+
+    Ok(written)
+}
+
+/// Renders the log file of a single project, which starts with a description of the result.
+fn render_project_log(record: &ProjectRecord) -> String {
+    let status = match record.status {
+        ProjectStatus::Succeeded => "succeeded",
+        ProjectStatus::Failed => "failed",
+        ProjectStatus::Skipped => "skipped",
+    };
+
+    let mut lines = vec![
+        format!("project: {}", record.name),
+        format!("path: {}", record.path.display()),
+        format!("status: {}", status),
+    ];
+    if let Some(code) = record.exit_code {
+        lines.push(format!("exit code: {}", code));
+    }
+    if let Some(error) = &record.error {
+        lines.push(format!("error: {}", error));
+    }
+    lines.push(format!("attempts: {}", record.attempts));
+    lines.push(format!(
+        "duration: {}",
+        format_duration_ms(record.duration_ms)
+    ));
+
+    for (name, output) in [("stdout", &record.stdout), ("stderr", &record.stderr)] {
+        lines.push(String::new());
+        lines.push(format!("--- {} ---", name));
+        lines.push(output.trim_end_matches('\n').to_string());
+    }
+    lines.push(String::new());
+
+    lines.join("\n")
+}
+
+/// Formats a duration in milliseconds, rounded to tenths of a second (like `1.2s`).
+fn format_duration_ms(duration_ms: u64) -> String {
+    format!("{:.1}s", Duration::from_millis(duration_ms).as_secs_f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(name: &str, status: ProjectStatus, exit_code: Option<i32>) -> ProjectRecord {
+        ProjectRecord {
+            name: name.to_string(),
+            path: PathBuf::from(format!("/repo/{}", name)),
+            status,
+            exit_code,
+            attempts: 1,
+            duration_ms: 1240,
+            stdout: "Resolving dependencies...\n".to_string(),
+            stderr: String::new(),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn renders_failed_projects_with_the_end_of_their_stderr() {
+        let records = [
+            record("app", ProjectStatus::Succeeded, Some(0)),
+            ProjectRecord {
+                stderr: "line 1\nline 2\nline 3\n".to_string(),
+                ..record("core", ProjectStatus::Failed, Some(65))
+            },
+            ProjectRecord {
+                error: Some("timed out after 30s".to_string()),
+                attempts: 3,
+                ..record("ui_kit", ProjectStatus::Failed, None)
+            },
+            record("utils", ProjectStatus::Skipped, None),
+        ];
+
+        assert_eq!(
+            render_failure_summary(&records, 2).unwrap(),
+            [
+                "The command failed in 2 of 4 projects:",
+                "",
+                "  PROJECT  PATH          EXIT  ATTEMPTS  DURATION",
+                "  core     /repo/core    65    1         1.2s",
+                "  ui_kit   /repo/ui_kit  -     3         1.2s",
+                "",
+                "core (last 2 of 3 lines of stderr):",
+                "  | line 2",
+                "  | line 3",
+                "",
+                "ui_kit (timed out after 30s):",
+            ]
+            .join("\n")
+        );
+        assert_eq!(render_failure_summary(&records[..1], 2), None);
+    }
+
+    #[test]
+    fn writes_a_log_file_for_every_project_that_ran() {
+        let dir = tempfile::tempdir().unwrap();
+        let records = [
+            record("app", ProjectStatus::Succeeded, Some(0)),
+            record("app", ProjectStatus::Failed, Some(1)),
+            record("core", ProjectStatus::Skipped, None),
+        ];
+
+        assert_eq!(write_project_logs(dir.path(), &records).unwrap(), 2);
+
+        let log = fs::read_to_string(dir.path().join("app-2.log")).unwrap();
+        assert!(log.starts_with("project: app\npath: /repo/app\nstatus: failed\nexit code: 1\n"));
+        assert!(log.contains("--- stdout ---\nResolving dependencies...\n"));
+        assert!(dir.path().join("app.log").exists());
+        assert!(!dir.path().join("core.log").exists());
+    }
+}
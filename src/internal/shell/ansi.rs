@@ -0,0 +1,74 @@
+use std::borrow::Cow;
+
+const ESC: char = '\u{1b}';
+const BEL: char = '\u{7}';
+
+/// Removes ANSI escape sequences (like colors and cursor movement) from the given text, along
+/// with anything a carriage return would overwrite on a terminal (like the earlier frames of a
+/// progress bar).
+///
+/// Returns the text unchanged (without allocating) when there is nothing to remove.
+pub fn strip_ansi(text: &str) -> Cow<'_, str> {
+    if !text.contains([ESC, '\r']) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ESC => match chars.next() {
+                // CSI: parameters and intermediates, ended by a byte in the range '@'..='~'.
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC (like hyperlinks and window titles): ended by BEL or ESC '\'.
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == BEL || (c == ESC && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                }
+                // All other escape sequences consist of a single character.
+                _ => {}
+            },
+            '\r' => {
+                // A trailing carriage return (like in CRLF line endings) does not overwrite
+                // anything.
+                if chars.peek().is_some_and(|next| *next != '\n') {
+                    let line_start = stripped.rfind('\n').map_or(0, |index| index + 1);
+                    stripped.truncate(line_start);
+                }
+            }
+            c => stripped.push(c),
+        }
+    }
+
+    Cow::Owned(stripped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_escape_sequences_and_overwritten_text() {
+        assert!(matches!(strip_ansi("plain text"), Cow::Borrowed(_)));
+        assert_eq!(
+            strip_ansi("\u{1b}[1;31merror\u{1b}[0m: failed"),
+            "error: failed"
+        );
+        assert_eq!(
+            strip_ansi("\u{1b}]8;;https://dart.dev\u{1b}\\docs\u{1b}]8;;\u{7} here"),
+            "docs here"
+        );
+        assert_eq!(strip_ansi("[=>  ] 10%\r[===>] 90%\rdone"), "done");
+        assert_eq!(strip_ansi("first\r\nsecond\r"), "first\nsecond");
+        assert_eq!(strip_ansi("a\nb\r\u{1b}[2Kc"), "a\nc");
+    }
+}
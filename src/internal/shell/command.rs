@@ -0,0 +1,128 @@
+use super::{OutputLine, Shell, ShellError, ShellOutput};
+use std::{path::PathBuf, time::Duration};
+
+/// A command to run on a `Shell`, along with everything that controls how it runs.
+///
+/// # Examples
+/// ```no_run
+/// use suitcase::internal::shell::{HostShell, ShellCommand};
+///
+/// let output = ShellCommand::new("dart")
+///     .args(["pub", "get"])
+///     .current_dir("/path/to/project")
+///     .env("PUB_CACHE", "/tmp/pub-cache")
+///     .run(&HostShell::new())
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShellCommand {
+    pub(super) program: String,
+    pub(super) args: Vec<String>,
+    pub(super) cwd: Option<PathBuf>,
+    pub(super) env_clear: bool,
+    pub(super) env: Vec<(String, EnvChange)>,
+    pub(super) stdin: Option<Vec<u8>>,
+    pub(super) inherit_terminal: bool,
+    pub(super) timeout: Option<Duration>,
+}
+
+/// A change to an environment variable of a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvChange {
+    Set(String),
+    Remove,
+}
+
+impl ShellCommand {
+    /// Creates a command that runs the given program without any args, in the current working
+    /// directory and with the environment of this process.
+    pub fn new(program: impl ToString) -> Self {
+        Self {
+            program: program.to_string(),
+            args: vec![],
+            cwd: None,
+            env_clear: false,
+            env: vec![],
+            stdin: None,
+            inherit_terminal: false,
+            timeout: None,
+        }
+    }
+
+    /// Adds an arg.
+    pub fn arg(mut self, arg: impl ToString) -> Self {
+        self.args.push(arg.to_string());
+        self
+    }
+
+    /// Adds multiple args.
+    pub fn args<S: ToString>(mut self, args: impl IntoIterator<Item = S>) -> Self {
+        self.args
+            .extend(args.into_iter().map(|arg| arg.to_string()));
+        self
+    }
+
+    /// Sets the working directory of the command. This does not change the working directory of
+    /// this process, which makes it safe to run commands from multiple threads at once.
+    pub fn current_dir(mut self, cwd: impl Into<PathBuf>) -> Self {
+        self.cwd = Some(cwd.into());
+        self
+    }
+
+    /// Sets an environment variable.
+    pub fn env(mut self, key: impl ToString, value: impl ToString) -> Self {
+        self.env
+            .push((key.to_string(), EnvChange::Set(value.to_string())));
+        self
+    }
+
+    /// Removes an environment variable.
+    pub fn env_remove(mut self, key: impl ToString) -> Self {
+        self.env.push((key.to_string(), EnvChange::Remove));
+        self
+    }
+
+    /// Starts the command without any environment variables, except for the ones set using
+    /// `env` (no matter whether they were set before or after calling this).
+    pub fn env_clear(mut self) -> Self {
+        self.env_clear = true;
+        self
+    }
+
+    /// Writes the given content to the stdin of the command (instead of leaving stdin empty).
+    pub fn stdin(mut self, content: impl Into<Vec<u8>>) -> Self {
+        self.stdin = Some(content.into());
+        self
+    }
+
+    /// Connects the command to the terminal of this process, so interactive tools can be used.
+    ///
+    /// The output of the command is shown directly instead of being captured (so the returned
+    /// `ShellOutput` is empty), and stdin content set using `stdin` is ignored.
+    pub fn inherit_terminal(mut self) -> Self {
+        self.inherit_terminal = true;
+        self
+    }
+
+    /// Kills the command (and every process it started) when it runs longer than the given
+    /// duration.
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Runs this command on the given shell and returns the result.
+    pub fn run(&self, shell: &dyn Shell) -> Result<ShellOutput, ShellError> {
+        shell.execute(self, &mut |_| {})
+    }
+
+    /// Runs this command on the given shell and returns the result, while passing every line of
+    /// stdout and stderr to `on_line` as soon as it is produced.
+    pub fn run_streaming(
+        &self,
+        shell: &dyn Shell,
+        on_line: &mut dyn FnMut(OutputLine),
+    ) -> Result<ShellOutput, ShellError> {
+        shell.execute(self, on_line)
+    }
+}
@@ -0,0 +1,124 @@
+use super::{exit_status, EnvChange, OutputLine, Shell, ShellCommand, ShellError, ShellOutput};
+use crate::internal::report::OutputFormat;
+use std::env;
+
+/// A `Shell` that does not run any commands, but prints the working directory and command line
+/// of every command it is asked to run and reports it as successful.
+///
+/// Commands use this shell for steps that change something when `--dry-run` is set, while
+/// read-only steps (like finding projects) still run on the regular shell.
+pub struct DryRunShell {
+    output: OutputFormat,
+}
+
+impl DryRunShell {
+    /// Creates a new `DryRunShell`. The steps are printed to stdout in the `Text` output format,
+    /// and to stderr otherwise (so machine-readable output is not interrupted).
+    pub fn new(output: OutputFormat) -> Self {
+        Self { output }
+    }
+
+    fn print_step(&self, command: &ShellCommand) {
+        let cwd = match &command.cwd {
+            Some(cwd) => cwd.display().to_string(),
+            None => env::current_dir()
+                .map(|cwd| cwd.display().to_string())
+                .unwrap_or_else(|_| ".".to_string()),
+        };
+
+        let step = format!("[dry-run] in {}: {}", cwd, command_line(command));
+        match self.output {
+            OutputFormat::Text => println!("{}", step),
+            OutputFormat::Json | OutputFormat::Ndjson => eprintln!("{}", step),
+        }
+    }
+}
+
+impl Shell for DryRunShell {
+    fn execute(
+        &self,
+        command: &ShellCommand,
+        _on_line: &mut dyn FnMut(OutputLine),
+    ) -> Result<ShellOutput, ShellError> {
+        self.print_step(command);
+
+        Ok(ShellOutput::new(exit_status(Some(0)), vec![], vec![]))
+    }
+}
+
+/// Formats the given command as a line that could be pasted into a POSIX shell, including its
+/// environment changes (using `env` when variables are removed or cleared).
+fn command_line(command: &ShellCommand) -> String {
+    let mut parts = vec![];
+    let needs_env = command.env_clear
+        || command
+            .env
+            .iter()
+            .any(|(_, change)| *change == EnvChange::Remove);
+    if needs_env {
+        parts.push("env".to_string());
+    }
+    if command.env_clear {
+        parts.push("-i".to_string());
+    }
+    for (key, change) in &command.env {
+        match change {
+            EnvChange::Set(value) => parts.push(format!("{}={}", key, quote(value))),
+            EnvChange::Remove => parts.push(format!("-u {}", quote(key))),
+        }
+    }
+
+    parts.extend(
+        std::iter::once(command.program.as_str())
+            .chain(command.args.iter().map(String::as_str))
+            .map(quote),
+    );
+    parts.join(" ")
+}
+
+/// Quotes the given argument for a POSIX shell if it contains any special characters.
+fn quote(arg: &str) -> String {
+    let is_safe = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c));
+
+    if is_safe {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_arguments_with_special_characters() {
+        assert_eq!(quote("flutter"), "flutter");
+        assert_eq!(quote("--define=FOO=bar"), "--define=FOO=bar");
+        assert_eq!(quote("echo hi"), "'echo hi'");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+        assert_eq!(quote(""), "''");
+    }
+
+    #[test]
+    fn formats_environment_changes() {
+        let command = ShellCommand::new("dart")
+            .args(["pub", "get"])
+            .env("PUB_CACHE", "/tmp/pub cache");
+        assert_eq!(
+            command_line(&command),
+            "PUB_CACHE='/tmp/pub cache' dart pub get"
+        );
+
+        let command = ShellCommand::new("flutter")
+            .env_remove("FLUTTER_ROOT")
+            .env("CI", "true");
+        assert_eq!(
+            command_line(&command),
+            "env -u FLUTTER_ROOT CI=true flutter"
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use log::{debug, warn};
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Mutex,
+};
+
+/// The exit code of a process that was terminated by Ctrl-C (128 + SIGINT).
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static CATCHING: AtomicUsize = AtomicUsize::new(0);
+static RUNNING_PROCESS_GROUPS: Mutex<Vec<u32>> = Mutex::new(vec![]);
+
+/// Installs a Ctrl-C handler for this process.
+///
+/// While an `InterruptGuard` (see `catch_interrupts`) is alive, the first Ctrl-C only marks the
+/// process as interrupted: `HostShell` forwards SIGINT to the commands it is running and starts no
+/// new ones, so the caller can still report partial results. Otherwise (or on a second Ctrl-C),
+/// every running command is killed and the process exits immediately.
+pub fn install_ctrl_c_handler() {
+    let result = ctrlc::set_handler(|| {
+        let already_interrupted = INTERRUPTED.swap(true, Ordering::SeqCst);
+        if already_interrupted || CATCHING.load(Ordering::SeqCst) == 0 {
+            kill_running_process_groups();
+            std::process::exit(INTERRUPTED_EXIT_CODE);
+        }
+
+        warn!("Interrupted, stopping running commands (press Ctrl-C again to exit immediately)...");
+    });
+
+    if let Err(err) = result {
+        debug!("failed to install Ctrl-C handler: {}", err);
+    }
+}
+
+/// Returns whether Ctrl-C was pressed while interrupts were being caught.
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Makes the first Ctrl-C interrupt the running commands instead of exiting the process, until
+/// the returned guard is dropped.
+pub fn catch_interrupts() -> InterruptGuard {
+    CATCHING.fetch_add(1, Ordering::SeqCst);
+    InterruptGuard { _private: () }
+}
+
+/// Catches Ctrl-C while it is alive. See `catch_interrupts`.
+pub struct InterruptGuard {
+    _private: (),
+}
+
+impl Drop for InterruptGuard {
+    fn drop(&mut self) {
+        CATCHING.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Keeps track of a process group started by `HostShell`, so it can be killed when the process
+/// exits because of Ctrl-C.
+pub(super) struct TrackedProcessGroup {
+    id: u32,
+}
+
+impl TrackedProcessGroup {
+    pub(super) fn new(id: u32) -> Self {
+        RUNNING_PROCESS_GROUPS.lock().unwrap().push(id);
+        Self { id }
+    }
+}
+
+impl Drop for TrackedProcessGroup {
+    fn drop(&mut self) {
+        RUNNING_PROCESS_GROUPS
+            .lock()
+            .unwrap()
+            .retain(|id| *id != self.id);
+    }
+}
+
+/// A signal sent to the process group of a running command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Signal {
+    /// Asks the command to stop, like pressing Ctrl-C in a terminal would.
+    Interrupt,
+
+    /// Stops the command immediately.
+    Kill,
+}
+
+/// Sends the given signal to every process in the given process group.
+#[cfg(unix)]
+pub(super) fn signal_process_group(id: u32, signal: Signal) {
+    let signal = match signal {
+        Signal::Interrupt => libc::SIGINT,
+        Signal::Kill => libc::SIGKILL,
+    };
+
+    // SAFETY: `kill` has no memory safety requirements. A negative pid targets the process group.
+    if unsafe { libc::kill(-(id as libc::pid_t), signal) } != 0 {
+        debug!(
+            "failed to signal process group {}: {}",
+            id,
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+fn kill_running_process_groups() {
+    #[cfg(unix)]
+    if let Ok(groups) = RUNNING_PROCESS_GROUPS.lock() {
+        for id in groups.iter() {
+            signal_process_group(*id, Signal::Kill);
+        }
+    }
+}
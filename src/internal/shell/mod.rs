@@ -0,0 +1,672 @@
+use log::debug;
+use std::{
+    borrow::Cow,
+    fmt,
+    io::{BufRead, BufReader, Read, Write},
+    path::Path,
+    process::{Child, ChildStdin, Command, ExitStatus, Stdio},
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+mod ansi;
+mod command;
+mod dry_run;
+mod interrupt;
+mod recording;
+mod scripted;
+
+pub use ansi::strip_ansi;
+pub use command::{EnvChange, ShellCommand};
+pub use dry_run::DryRunShell;
+pub use interrupt::{catch_interrupts, install_ctrl_c_handler, is_interrupted, InterruptGuard};
+pub use recording::RecordingShell;
+pub use scripted::{Invocation, InvocationResult, ScriptedShell};
+
+/// An abstraction around a shell that can run commands.
+///
+/// `HostShell` runs commands on the host system and `DryRunShell` only prints them, while
+/// `ScriptedShell` and `RecordingShell` make it possible to test commands without spawning real
+/// processes.
+pub trait Shell: Sync {
+    /// Runs the given command and returns the result, while passing every line of stdout and
+    /// stderr to `on_line` as soon as it is produced.
+    ///
+    /// The returned `ShellOutput` (or `ShellError`) still contains the full stdout and stderr of
+    /// the command.
+    ///
+    /// Unless the command inherits the terminal, it runs in its own process group. When it runs
+    /// longer than its timeout, the whole group is killed and `ShellError::Timeout` is returned.
+    /// When Ctrl-C is pressed while interrupts are caught (see `catch_interrupts`), SIGINT is
+    /// forwarded to the group and `ShellError::Interrupted` is returned.
+    fn execute(
+        &self,
+        command: &ShellCommand,
+        on_line: &mut dyn FnMut(OutputLine),
+    ) -> Result<ShellOutput, ShellError>;
+
+    /// Run the given command with the given args (if any) and returns the result.
+    fn run_command(&self, cmd: String, args: Vec<String>) -> Result<ShellOutput, ShellError> {
+        self.run_command_in(None, cmd, args)
+    }
+
+    /// Run the given command with the given args (if any) in the given working directory and
+    /// returns the result.
+    ///
+    /// When `cwd` is `None`, the command runs in the current working directory of this process.
+    /// Passing a directory does not change the working directory of this process, which makes
+    /// this safe to call from multiple threads at once.
+    fn run_command_in(
+        &self,
+        cwd: Option<&Path>,
+        cmd: String,
+        args: Vec<String>,
+    ) -> Result<ShellOutput, ShellError> {
+        let mut command = ShellCommand::new(cmd).args(args);
+        if let Some(cwd) = cwd {
+            command = command.current_dir(cwd);
+        }
+
+        self.execute(&command, &mut |_| {})
+    }
+}
+
+/// How often a running command checks whether it timed out or was interrupted while it does not
+/// produce any output.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long an interrupted command may take to stop before its process group is killed.
+const INTERRUPT_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Why a running command was stopped before it finished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StopReason {
+    TimedOut,
+    Interrupted { at: Instant },
+}
+
+/// A `Shell` that runs commands on the host system.
+#[derive(Default)]
+pub struct HostShell {}
+
+impl HostShell {
+    /// Creates a new `HostShell`.
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Indents the given string by the given number of spaces.
+    fn indent(spaces: usize, string: &str) -> String {
+        let indent = " ".repeat(spaces);
+        string
+            .lines()
+            .map(|line| format!("{}{}", indent, line))
+            .collect::<Vec<_>>()
+            .join("\r")
+    }
+
+    /// Creates a `Command` for the given `ShellCommand`.
+    fn build_command(command: &ShellCommand) -> Command {
+        let (cmd, args) = (&command.program, command.args.join(" "));
+        match &command.cwd {
+            Some(cwd) => debug!("running command in {:?}: {} {}", cwd, cmd, args),
+            None => debug!("running command: {} {}", cmd, args),
+        }
+
+        let mut process = Command::new(cmd);
+        process.args(&command.args);
+        if let Some(cwd) = &command.cwd {
+            process.current_dir(cwd);
+        }
+        if command.env_clear {
+            process.env_clear();
+        }
+        for (key, change) in &command.env {
+            match change {
+                EnvChange::Set(value) => process.env(key, value),
+                EnvChange::Remove => process.env_remove(key),
+            };
+        }
+
+        if command.inherit_terminal {
+            process
+                .stdin(Stdio::inherit())
+                .stdout(Stdio::inherit())
+                .stderr(Stdio::inherit());
+        } else {
+            process
+                .stdin(match command.stdin {
+                    Some(_) => Stdio::piped(),
+                    None => Stdio::null(),
+                })
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped());
+            // A separate process group keeps the terminal's Ctrl-C away from the command (so it
+            // can be forwarded instead) and makes it possible to kill everything it started.
+            #[cfg(unix)]
+            std::os::unix::process::CommandExt::process_group(&mut process, 0);
+        }
+
+        process
+    }
+
+    /// Spawns a thread that reads the given stream line by line and sends the raw bytes of every
+    /// line (including the trailing newline, if any) to the given sender.
+    fn forward_lines(
+        stream: Option<impl Read + Send + 'static>,
+        kind: OutputStream,
+        sender: &Sender<(OutputStream, Vec<u8>)>,
+    ) -> JoinHandle<()> {
+        let sender = sender.clone();
+        thread::spawn(move || {
+            let Some(stream) = stream else {
+                return;
+            };
+
+            let mut reader = BufReader::new(stream);
+            loop {
+                let mut line = vec![];
+                match reader.read_until(b'\n', &mut line) {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {}
+                }
+                if sender.send((kind, line)).is_err() {
+                    break;
+                }
+            }
+        })
+    }
+
+    /// Spawns a thread that writes the given content to the given stdin and then closes it.
+    fn write_stdin(stdin: Option<ChildStdin>, content: Vec<u8>) -> JoinHandle<()> {
+        thread::spawn(move || {
+            let Some(mut stdin) = stdin else {
+                return;
+            };
+
+            // The command may exit without reading all of its stdin, which is not an error.
+            if let Err(err) = stdin.write_all(&content) {
+                debug!("stopped writing to stdin of command: {}", err);
+            }
+        })
+    }
+
+    /// Sends the given signal to the process group of the given child (or kills the child on
+    /// platforms without process groups).
+    fn stop(child: &mut Child, signal: interrupt::Signal) {
+        #[cfg(unix)]
+        interrupt::signal_process_group(child.id(), signal);
+
+        #[cfg(not(unix))]
+        {
+            let _ = signal;
+            let _ = child.kill();
+        }
+    }
+
+    /// Waits for a command that inherits the terminal, killing it once the given deadline passes.
+    /// Ctrl-C reaches such commands directly through the terminal.
+    fn wait_in_terminal(
+        child: &mut Child,
+        deadline: Option<Instant>,
+    ) -> std::io::Result<(ExitStatus, Option<StopReason>)> {
+        let Some(deadline) = deadline else {
+            return Ok((child.wait()?, None));
+        };
+
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return Ok((status, None));
+            }
+            if Instant::now() >= deadline {
+                child.kill()?;
+                return Ok((child.wait()?, Some(StopReason::TimedOut)));
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Logs the output of a finished command and converts it into a result based on its status.
+    fn finish(
+        cmd: String,
+        args: Vec<String>,
+        output: ShellOutput,
+    ) -> Result<ShellOutput, ShellError> {
+        debug!("command status: {}", output.status);
+        debug!(
+            "command output:\n  stdout:\n{}\n\n  stderr:\n{}",
+            if output.stdout.is_empty() {
+                HostShell::indent(4, "<NO STDOUT OUTPUT>")
+            } else {
+                format!("\n{}", HostShell::indent(4, &output.stdout_lossy()))
+            },
+            if output.stderr.is_empty() {
+                HostShell::indent(4, "<NO STDERR OUTPUT>")
+            } else {
+                format!("\n{}", HostShell::indent(4, &output.stderr_lossy()))
+            }
+        );
+
+        if output.status.success() {
+            Ok(output)
+        } else {
+            Err(ShellError::HostProcessExecutionFailure {
+                command: cmd,
+                args: args.join(" "),
+                status: output.status,
+                stdout: output.stdout_lossy().into_owned(),
+                stderr: output.stderr_lossy().into_owned(),
+            })
+        }
+    }
+}
+
+impl Shell for HostShell {
+    fn execute(
+        &self,
+        command: &ShellCommand,
+        on_line: &mut dyn FnMut(OutputLine),
+    ) -> Result<ShellOutput, ShellError> {
+        let (cmd, args) = (command.program.clone(), command.args.clone());
+        if is_interrupted() {
+            return Err(ShellError::Interrupted {
+                command: cmd,
+                args: args.join(" "),
+                stdout: String::new(),
+                stderr: String::new(),
+            });
+        }
+
+        let start_failure = |err: std::io::Error| ShellError::ShellStartFailure {
+            command: cmd.clone(),
+            args: args.join(" "),
+            error: err.to_string(),
+        };
+
+        let mut child = HostShell::build_command(command)
+            .spawn()
+            .map_err(start_failure)?;
+        let deadline = command.timeout.map(|timeout| Instant::now() + timeout);
+
+        let (status, stdout, stderr, stop_reason) = if command.inherit_terminal {
+            let (status, stop_reason) =
+                HostShell::wait_in_terminal(&mut child, deadline).map_err(start_failure)?;
+            (status, vec![], vec![], stop_reason)
+        } else {
+            let _tracked_group = interrupt::TrackedProcessGroup::new(child.id());
+            let stdin_writer = command
+                .stdin
+                .clone()
+                .map(|content| HostShell::write_stdin(child.stdin.take(), content));
+
+            let (sender, receiver) = mpsc::channel();
+            let stdout_reader =
+                HostShell::forward_lines(child.stdout.take(), OutputStream::Stdout, &sender);
+            let stderr_reader =
+                HostShell::forward_lines(child.stderr.take(), OutputStream::Stderr, &sender);
+            drop(sender);
+
+            let mut stdout = vec![];
+            let mut stderr = vec![];
+            let mut stop_reason = None;
+            loop {
+                match receiver.recv_timeout(POLL_INTERVAL) {
+                    Ok((kind, line)) => {
+                        let (output, wrap): (_, fn(String) -> OutputLine) = match kind {
+                            OutputStream::Stdout => (&mut stdout, OutputLine::Stdout),
+                            OutputStream::Stderr => (&mut stderr, OutputLine::Stderr),
+                        };
+                        output.extend_from_slice(&line);
+                        let line = line.strip_suffix(b"\n").unwrap_or(&line);
+                        on_line(wrap(String::from_utf8_lossy(line).into_owned()));
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+
+                match stop_reason {
+                    None if deadline.is_some_and(|deadline| Instant::now() >= deadline) => {
+                        debug!("command timed out, killing it: {} {}", cmd, args.join(" "));
+                        HostShell::stop(&mut child, interrupt::Signal::Kill);
+                        stop_reason = Some(StopReason::TimedOut);
+                    }
+                    None if is_interrupted() => {
+                        debug!(
+                            "forwarding interrupt to command: {} {}",
+                            cmd,
+                            args.join(" ")
+                        );
+                        HostShell::stop(&mut child, interrupt::Signal::Interrupt);
+                        stop_reason = Some(StopReason::Interrupted { at: Instant::now() });
+                    }
+                    Some(StopReason::Interrupted { at })
+                        if at.elapsed() >= INTERRUPT_GRACE_PERIOD =>
+                    {
+                        debug!("interrupted command did not stop, killing it");
+                        HostShell::stop(&mut child, interrupt::Signal::Kill);
+                        stop_reason = Some(StopReason::TimedOut);
+                    }
+                    _ => {}
+                }
+            }
+
+            stdout_reader.join().unwrap();
+            stderr_reader.join().unwrap();
+            let status = child.wait().map_err(start_failure)?;
+            if let Some(stdin_writer) = stdin_writer {
+                stdin_writer.join().unwrap();
+            }
+
+            (status, stdout, stderr, stop_reason)
+        };
+
+        let output = ShellOutput::new(status, stdout, stderr);
+        match stop_reason {
+            None => HostShell::finish(cmd, args, output),
+            Some(StopReason::TimedOut) if !is_interrupted() => Err(ShellError::Timeout {
+                command: cmd,
+                args: args.join(" "),
+                // The deadline is only reached when a timeout was given.
+                timeout: command.timeout.unwrap_or_default(),
+                stdout: output.stdout_lossy().into_owned(),
+                stderr: output.stderr_lossy().into_owned(),
+            }),
+            Some(_) => Err(ShellError::Interrupted {
+                command: cmd,
+                args: args.join(" "),
+                stdout: output.stdout_lossy().into_owned(),
+                stderr: output.stderr_lossy().into_owned(),
+            }),
+        }
+    }
+}
+
+/// Creates an `ExitStatus` for a process that exited with the given code, or that was terminated
+/// by a signal when `code` is `None`.
+fn exit_status(code: Option<i32>) -> ExitStatus {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        match code {
+            Some(code) => ExitStatus::from_raw((code & 0xff) << 8),
+            None => ExitStatus::from_raw(9),
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::ExitStatusExt;
+        ExitStatus::from_raw(code.unwrap_or(1) as u32)
+    }
+}
+
+/// The result of a command that ran to completion.
+///
+/// The output is kept as the raw bytes the command wrote, since commands are not guaranteed to
+/// write valid UTF-8. Use the lossy accessors for output that is only displayed, and the strict
+/// ones for output that is parsed.
+#[derive(Debug)]
+pub struct ShellOutput {
+    pub status: std::process::ExitStatus,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+impl ShellOutput {
+    /// Creates a new `ShellOutput` with the given status and raw output.
+    pub fn new(status: ExitStatus, stdout: impl Into<Vec<u8>>, stderr: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            stdout: stdout.into(),
+            stderr: stderr.into(),
+        }
+    }
+
+    /// Returns the raw bytes the command wrote to stdout.
+    pub fn stdout_bytes(&self) -> &[u8] {
+        &self.stdout
+    }
+
+    /// Returns the raw bytes the command wrote to stderr.
+    pub fn stderr_bytes(&self) -> &[u8] {
+        &self.stderr
+    }
+
+    /// Returns the stdout of the command, replacing invalid UTF-8 with `U+FFFD`.
+    pub fn stdout_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.stdout)
+    }
+
+    /// Returns the stderr of the command, replacing invalid UTF-8 with `U+FFFD`.
+    pub fn stderr_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.stderr)
+    }
+
+    /// Returns the stdout of the command, or an error if it is not valid UTF-8.
+    pub fn stdout_utf8(&self) -> Result<&str, ShellError> {
+        Self::decode(OutputStream::Stdout, &self.stdout)
+    }
+
+    /// Returns the stderr of the command, or an error if it is not valid UTF-8.
+    pub fn stderr_utf8(&self) -> Result<&str, ShellError> {
+        Self::decode(OutputStream::Stderr, &self.stderr)
+    }
+
+    fn decode(stream: OutputStream, bytes: &[u8]) -> Result<&str, ShellError> {
+        std::str::from_utf8(bytes)
+            .map_err(|error| ShellError::InvalidOutputEncoding { stream, error })
+    }
+}
+
+/// One of the output streams of a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+impl fmt::Display for OutputStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputStream::Stdout => write!(f, "stdout"),
+            OutputStream::Stderr => write!(f, "stderr"),
+        }
+    }
+}
+
+/// A single line of output produced by a running command (with invalid UTF-8 replaced by
+/// `U+FFFD`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputLine {
+    /// A line that was written to stdout.
+    Stdout(String),
+
+    /// A line that was written to stderr.
+    Stderr(String),
+}
+
+impl OutputLine {
+    /// Returns this line with ANSI escape sequences removed (see `strip_ansi`).
+    pub fn without_ansi(self) -> Self {
+        match self {
+            OutputLine::Stdout(line) => OutputLine::Stdout(strip_ansi(&line).into_owned()),
+            OutputLine::Stderr(line) => OutputLine::Stderr(strip_ansi(&line).into_owned()),
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone)]
+pub enum ShellError {
+    #[error("failed to execute command (ran: '{command} {args}', got status: {status}, stdout: '{stdout}', stderr: '{stderr}')")]
+    HostProcessExecutionFailure {
+        command: String,
+        args: String,
+        status: ExitStatus,
+        stdout: String,
+        stderr: String,
+    },
+
+    #[error("failed to start shell: {error} (ran: '{command} {args}')")]
+    ShellStartFailure {
+        command: String,
+        args: String,
+        error: String,
+    },
+
+    #[error("command timed out after {} and was killed (ran: '{command} {args}', stdout: '{stdout}', stderr: '{stderr}')", humantime::format_duration(*.timeout))]
+    Timeout {
+        command: String,
+        args: String,
+        timeout: Duration,
+        stdout: String,
+        stderr: String,
+    },
+
+    #[error(
+        "command was interrupted (ran: '{command} {args}', stdout: '{stdout}', stderr: '{stderr}')"
+    )]
+    Interrupted {
+        command: String,
+        args: String,
+        stdout: String,
+        stderr: String,
+    },
+
+    #[error("command wrote invalid UTF-8 to {stream}: {error}")]
+    InvalidOutputEncoding {
+        stream: OutputStream,
+        error: std::str::Utf8Error,
+    },
+}
+
+/// A macro that attempts to convert all given arguments into a `String`
+/// for use as a command line argument by calling `to_string()` on each one.
+///
+/// # Examples
+/// ```
+/// use suitcase::args;
+///
+/// let args: Vec<String> = args!["foo", "bar", 42];
+/// assert_eq!(args, vec!["foo".to_string(), "bar".to_string(), "42".to_string()]);
+/// ```
+#[macro_export]
+macro_rules! args {
+    ($($arg:expr),*) => {
+        vec![$($arg.to_string()),*]
+    };
+}
+
+/// A macro that runs the given command with the given args (if any)
+/// on the given shell and returns the result.
+///
+/// # Examples
+/// ```no_run
+/// use suitcase::exec_on;
+///
+/// let shell = suitcase::internal::shell::HostShell::new();
+///
+/// let result = exec_on!(shell, "echo", "hello world").unwrap();
+/// assert_eq!(result.stdout_utf8().unwrap(), "hello world\n");
+/// ```
+#[macro_export]
+macro_rules! exec_on {
+    ($shell:expr, $cmd:expr) => {{
+        #[allow(unused_imports)]
+        use $crate::internal::shell::Shell as _;
+        $shell.run_command($cmd.to_string(), vec![])
+    }};
+    ($shell:expr, $cmd:expr, $($arg:expr),*) => {{
+        #[allow(unused_imports)]
+        use $crate::internal::shell::Shell as _;
+        $shell.run_command($cmd.to_string(), $crate::args![$($arg),*])
+    }};
+}
+
+/// A macro that runs the given command with the given args (if any)
+/// on the given shell in the given working directory and returns the result.
+///
+/// Unlike `exec_on!`, this does not depend on (or change) the current working
+/// directory of the process.
+///
+/// # Examples
+/// ```no_run
+/// use std::path::Path;
+/// use suitcase::exec_in;
+///
+/// let shell = suitcase::internal::shell::HostShell::new();
+///
+/// let result = exec_in!(shell, Path::new("/tmp"), "pwd").unwrap();
+/// assert_eq!(result.stdout_utf8().unwrap(), "/tmp\n");
+/// ```
+#[macro_export]
+macro_rules! exec_in {
+    ($shell:expr, $cwd:expr, $cmd:expr) => {{
+        #[allow(unused_imports)]
+        use $crate::internal::shell::Shell as _;
+        $shell.run_command_in(Some($cwd), $cmd.to_string(), vec![])
+    }};
+    ($shell:expr, $cwd:expr, $cmd:expr, $($arg:expr),*) => {{
+        #[allow(unused_imports)]
+        use $crate::internal::shell::Shell as _;
+        $shell.run_command_in(Some($cwd), $cmd.to_string(), $crate::args![$($arg),*])
+    }};
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kills_the_process_group_of_commands_that_time_out() {
+        let started_at = Instant::now();
+        let mut lines = vec![];
+        let result = ShellCommand::new("bash")
+            .args(["-c", "echo started; sleep 10 & sleep 10"])
+            .timeout(Some(Duration::from_millis(300)))
+            .run_streaming(&HostShell::new(), &mut |line| lines.push(line));
+
+        // The background `sleep` keeps stdout open, so this only returns this quickly when the
+        // whole process group was killed.
+        assert!(started_at.elapsed() < Duration::from_secs(5));
+        assert!(matches!(
+            result,
+            Err(ShellError::Timeout { stdout, .. }) if stdout == "started\n"
+        ));
+        assert_eq!(lines, vec![OutputLine::Stdout("started".to_string())]);
+    }
+
+    #[test]
+    fn does_not_add_a_trailing_newline_to_output() {
+        let mut lines = vec![];
+        let output = ShellCommand::new("printf")
+            .arg("first\\nno-newline")
+            .run_streaming(&HostShell::new(), &mut |line| lines.push(line))
+            .unwrap();
+
+        assert_eq!(output.stdout_bytes(), b"first\nno-newline");
+        assert_eq!(
+            lines,
+            vec![
+                OutputLine::Stdout("first".to_string()),
+                OutputLine::Stdout("no-newline".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_output_that_is_not_valid_utf8() {
+        let output = exec_on!(HostShell::new(), "printf", r"caf\351\n").unwrap();
+
+        assert_eq!(output.stdout_bytes(), b"caf\xe9\n");
+        assert_eq!(output.stdout_lossy(), "caf\u{fffd}\n");
+        assert!(matches!(
+            output.stdout_utf8(),
+            Err(ShellError::InvalidOutputEncoding {
+                stream: OutputStream::Stdout,
+                ..
+            })
+        ));
+    }
+}
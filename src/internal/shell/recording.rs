@@ -0,0 +1,106 @@
+use super::{Invocation, OutputLine, Shell, ShellCommand, ShellError, ShellOutput};
+use anyhow::Context;
+use std::{path::PathBuf, sync::Mutex};
+
+/// A `Shell` that runs commands on another shell and records every invocation along with its
+/// result, so they can be replayed by `ScriptedShell::from_fixture`.
+///
+/// The recorded invocations are written to the fixture file when the shell is dropped, so the
+/// fixture is also written when a command fails.
+pub struct RecordingShell<S: Shell> {
+    inner: S,
+    fixture_path: PathBuf,
+    invocations: Mutex<Vec<Invocation>>,
+}
+
+impl<S: Shell> RecordingShell<S> {
+    /// Creates a new `RecordingShell` that runs commands on `inner` and records them to the
+    /// fixture file at `fixture_path`.
+    pub fn new(inner: S, fixture_path: PathBuf) -> Self {
+        Self {
+            inner,
+            fixture_path,
+            invocations: Mutex::new(vec![]),
+        }
+    }
+
+    /// Returns the invocations that have been recorded so far.
+    pub fn invocations(&self) -> Vec<Invocation> {
+        self.invocations.lock().unwrap().clone()
+    }
+
+    /// Writes all invocations that have been recorded so far to the fixture file.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(&self.invocations())?;
+        std::fs::write(&self.fixture_path, json + "\n").context(format!(
+            "trying to write shell fixture to '{}'",
+            self.fixture_path.display()
+        ))
+    }
+
+    fn record(&self, command: &ShellCommand, result: &Result<ShellOutput, ShellError>) {
+        self.invocations
+            .lock()
+            .unwrap()
+            .push(Invocation::from_result(command, result));
+    }
+}
+
+impl<S: Shell> Shell for RecordingShell<S> {
+    fn execute(
+        &self,
+        command: &ShellCommand,
+        on_line: &mut dyn FnMut(OutputLine),
+    ) -> Result<ShellOutput, ShellError> {
+        let result = self.inner.execute(command, on_line);
+        self.record(command, &result);
+        result
+    }
+}
+
+impl<S: Shell> Drop for RecordingShell<S> {
+    fn drop(&mut self) {
+        match self.save() {
+            Ok(()) => log::debug!(
+                "recorded shell invocations to '{}'",
+                self.fixture_path.display()
+            ),
+            Err(err) => log::error!("failed to record shell invocations: {:?}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        exec_on,
+        internal::shell::{Invocation, ScriptedShell},
+    };
+
+    #[test]
+    fn records_invocations_that_can_be_replayed() {
+        let fixture_path = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+
+        let shell = RecordingShell::new(
+            ScriptedShell::new()
+                .expect(Invocation::new("git", ["status"]).stdout("clean\n"))
+                .expect(Invocation::new("open", ["x"]).start_failure("nope")),
+            fixture_path.to_path_buf(),
+        );
+        exec_on!(shell, "git", "status").unwrap();
+        exec_on!(shell, "open", "x").unwrap_err();
+        drop(shell);
+
+        let replayed = ScriptedShell::from_fixture(&fixture_path).unwrap();
+        assert_eq!(
+            exec_on!(replayed, "git", "status")
+                .unwrap()
+                .stdout_utf8()
+                .unwrap(),
+            "clean\n"
+        );
+        assert!(exec_on!(replayed, "open", "x").is_err());
+        replayed.assert_all_used();
+    }
+}
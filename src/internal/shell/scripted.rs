@@ -0,0 +1,515 @@
+use super::{
+    exit_status, EnvChange, HostShell, OutputLine, Shell, ShellCommand, ShellError, ShellOutput,
+};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
+
+/// A single command invocation along with its result, as replayed by `ScriptedShell` and
+/// recorded by `RecordingShell`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+    /// The working directory the command runs in (`None` for the current working directory).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<PathBuf>,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// The environment variables the command sets (or removes, when `None`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, Option<String>>,
+    /// The content written to the stdin of the command.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdin: Option<String>,
+    pub result: InvocationResult,
+}
+
+/// The result of a command invocation.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum InvocationResult {
+    /// The command ran and exited with the given code (or was terminated by a signal if `code` is
+    /// `None`).
+    Exited {
+        code: Option<i32>,
+        #[serde(default)]
+        stdout: String,
+        #[serde(default)]
+        stderr: String,
+    },
+
+    /// The command could not be started.
+    StartFailure { error: String },
+
+    /// The command ran longer than the given timeout and was killed.
+    TimedOut {
+        timeout_ms: u64,
+        #[serde(default)]
+        stdout: String,
+        #[serde(default)]
+        stderr: String,
+    },
+
+    /// The command was interrupted by Ctrl-C.
+    Interrupted {
+        #[serde(default)]
+        stdout: String,
+        #[serde(default)]
+        stderr: String,
+    },
+}
+
+impl InvocationResult {
+    /// Returns the stdout and stderr of the command, if it was started.
+    fn output_mut(&mut self) -> Option<(&mut String, &mut String)> {
+        match self {
+            InvocationResult::Exited { stdout, stderr, .. }
+            | InvocationResult::TimedOut { stdout, stderr, .. }
+            | InvocationResult::Interrupted { stdout, stderr } => Some((stdout, stderr)),
+            InvocationResult::StartFailure { .. } => None,
+        }
+    }
+}
+
+impl Invocation {
+    /// Creates an invocation of the given command in the current working directory that exits
+    /// successfully without any output.
+    pub fn new<S: ToString>(command: &str, args: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            cwd: None,
+            command: command.to_string(),
+            args: args.into_iter().map(|arg| arg.to_string()).collect(),
+            env: BTreeMap::new(),
+            stdin: None,
+            result: InvocationResult::Exited {
+                code: Some(0),
+                stdout: String::new(),
+                stderr: String::new(),
+            },
+        }
+    }
+
+    /// Sets the working directory the command is expected to run in.
+    pub fn in_dir(mut self, cwd: impl Into<PathBuf>) -> Self {
+        self.cwd = Some(cwd.into());
+        self
+    }
+
+    /// Sets an environment variable the command is expected to set.
+    pub fn env(mut self, key: &str, value: &str) -> Self {
+        self.env.insert(key.to_string(), Some(value.to_string()));
+        self
+    }
+
+    /// Sets an environment variable the command is expected to remove.
+    pub fn env_remove(mut self, key: &str) -> Self {
+        self.env.insert(key.to_string(), None);
+        self
+    }
+
+    /// Sets the content the command is expected to receive on stdin.
+    pub fn stdin(mut self, content: &str) -> Self {
+        self.stdin = Some(content.to_string());
+        self
+    }
+
+    /// Sets the stdout the command produces.
+    pub fn stdout(mut self, output: &str) -> Self {
+        if let Some((stdout, _)) = self.result.output_mut() {
+            *stdout = output.to_string();
+        }
+        self
+    }
+
+    /// Sets the stderr the command produces.
+    pub fn stderr(mut self, output: &str) -> Self {
+        if let Some((_, stderr)) = self.result.output_mut() {
+            *stderr = output.to_string();
+        }
+        self
+    }
+
+    /// Sets the exit code of the command.
+    pub fn exit_code(mut self, exit_code: i32) -> Self {
+        if let InvocationResult::Exited { code, .. } = &mut self.result {
+            *code = Some(exit_code);
+        }
+        self
+    }
+
+    /// Makes the command fail to start with the given error.
+    pub fn start_failure(mut self, error: &str) -> Self {
+        self.result = InvocationResult::StartFailure {
+            error: error.to_string(),
+        };
+        self
+    }
+
+    /// Makes the command run longer than the given timeout (and be killed), which the command is
+    /// expected to be run with. Output that was set before is kept.
+    pub fn timed_out(mut self, timeout: Duration) -> Self {
+        let (stdout, stderr) = self.take_output();
+        self.result = InvocationResult::TimedOut {
+            timeout_ms: timeout.as_millis() as u64,
+            stdout,
+            stderr,
+        };
+        self
+    }
+
+    /// Makes the command get interrupted by Ctrl-C. Output that was set before is kept.
+    pub fn interrupted(mut self) -> Self {
+        let (stdout, stderr) = self.take_output();
+        self.result = InvocationResult::Interrupted { stdout, stderr };
+        self
+    }
+
+    fn take_output(&mut self) -> (String, String) {
+        self.result
+            .output_mut()
+            .map(|(stdout, stderr)| (std::mem::take(stdout), std::mem::take(stderr)))
+            .unwrap_or_default()
+    }
+
+    /// Creates an invocation from the result of running a command on another shell.
+    ///
+    /// Fixtures store output as text, so output that is not valid UTF-8 is recorded lossily.
+    pub(super) fn from_result(
+        command: &ShellCommand,
+        result: &Result<ShellOutput, ShellError>,
+    ) -> Self {
+        let result = match result {
+            Ok(output) => InvocationResult::Exited {
+                code: output.status.code(),
+                stdout: output.stdout_lossy().into_owned(),
+                stderr: output.stderr_lossy().into_owned(),
+            },
+            Err(ShellError::HostProcessExecutionFailure {
+                status,
+                stdout,
+                stderr,
+                ..
+            }) => InvocationResult::Exited {
+                code: status.code(),
+                stdout: stdout.clone(),
+                stderr: stderr.clone(),
+            },
+            Err(ShellError::ShellStartFailure { error, .. }) => InvocationResult::StartFailure {
+                error: error.clone(),
+            },
+            Err(ShellError::Timeout {
+                timeout,
+                stdout,
+                stderr,
+                ..
+            }) => InvocationResult::TimedOut {
+                timeout_ms: timeout.as_millis() as u64,
+                stdout: stdout.clone(),
+                stderr: stderr.clone(),
+            },
+            Err(ShellError::Interrupted { stdout, stderr, .. }) => InvocationResult::Interrupted {
+                stdout: stdout.clone(),
+                stderr: stderr.clone(),
+            },
+            // Shells never return decoding errors themselves (only `ShellOutput` does), but record
+            // them as a failure rather than panicking.
+            Err(error @ ShellError::InvalidOutputEncoding { .. }) => {
+                InvocationResult::StartFailure {
+                    error: error.to_string(),
+                }
+            }
+        };
+
+        Self {
+            cwd: command.cwd.clone(),
+            command: command.program.clone(),
+            args: command.args.clone(),
+            env: Self::env_of(command),
+            stdin: Self::stdin_of(command),
+            result,
+        }
+    }
+
+    /// Returns the environment changes of the given command, where later changes to the same
+    /// variable win.
+    fn env_of(command: &ShellCommand) -> BTreeMap<String, Option<String>> {
+        command
+            .env
+            .iter()
+            .map(|(key, change)| {
+                let value = match change {
+                    EnvChange::Set(value) => Some(value.clone()),
+                    EnvChange::Remove => None,
+                };
+                (key.clone(), value)
+            })
+            .collect()
+    }
+
+    fn stdin_of(command: &ShellCommand) -> Option<String> {
+        command
+            .stdin
+            .as_ref()
+            .map(|stdin| String::from_utf8_lossy(stdin).into_owned())
+    }
+
+    fn matches(&self, command: &ShellCommand) -> bool {
+        self.cwd == command.cwd
+            && self.command == command.program
+            && self.args == command.args
+            && self.env == Self::env_of(command)
+            && self.stdin == Self::stdin_of(command)
+            && match &self.result {
+                InvocationResult::TimedOut { timeout_ms, .. } => {
+                    command.timeout.map(|timeout| timeout.as_millis() as u64) == Some(*timeout_ms)
+                }
+                _ => true,
+            }
+    }
+
+    /// Returns the result of this invocation, as a real shell would.
+    fn replay(&self) -> Result<ShellOutput, ShellError> {
+        match &self.result {
+            InvocationResult::Exited {
+                code,
+                stdout,
+                stderr,
+            } => HostShell::finish(
+                self.command.clone(),
+                self.args.clone(),
+                ShellOutput::new(exit_status(*code), stdout.clone(), stderr.clone()),
+            ),
+            InvocationResult::StartFailure { error } => Err(ShellError::ShellStartFailure {
+                command: self.command.clone(),
+                args: self.args.join(" "),
+                error: error.clone(),
+            }),
+            InvocationResult::TimedOut {
+                timeout_ms,
+                stdout,
+                stderr,
+            } => Err(ShellError::Timeout {
+                command: self.command.clone(),
+                args: self.args.join(" "),
+                timeout: Duration::from_millis(*timeout_ms),
+                stdout: stdout.clone(),
+                stderr: stderr.clone(),
+            }),
+            InvocationResult::Interrupted { stdout, stderr } => Err(ShellError::Interrupted {
+                command: self.command.clone(),
+                args: self.args.join(" "),
+                stdout: stdout.clone(),
+                stderr: stderr.clone(),
+            }),
+        }
+    }
+}
+
+/// A `Shell` that does not run any processes, but replays the results of expected invocations.
+///
+/// Every command that is run must match the command, args and working directory of one of the
+/// remaining expected invocations, which is then used up. Invocations may be matched in any
+/// order, so commands that run projects in parallel can be tested as well. Running a command
+/// that was not expected panics.
+#[derive(Default)]
+pub struct ScriptedShell {
+    expected: Mutex<Vec<Invocation>>,
+}
+
+impl ScriptedShell {
+    /// Creates a new `ScriptedShell` that does not expect any invocations.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new `ScriptedShell` that expects the invocations in the given fixture file, as
+    /// written by `RecordingShell`.
+    pub fn from_fixture(path: &Path) -> anyhow::Result<Self> {
+        let file = std::fs::File::open(path).context(format!(
+            "trying to open shell fixture at path '{}'",
+            path.display()
+        ))?;
+        let invocations: Vec<Invocation> = serde_json::from_reader(file).context(format!(
+            "trying to parse shell fixture at path '{}'",
+            path.display()
+        ))?;
+
+        Ok(Self {
+            expected: Mutex::new(invocations),
+        })
+    }
+
+    /// Adds an expected invocation.
+    pub fn expect(self, invocation: Invocation) -> Self {
+        self.expected.lock().unwrap().push(invocation);
+        self
+    }
+
+    /// Returns the expected invocations that have not been used yet.
+    pub fn remaining(&self) -> Vec<Invocation> {
+        self.expected.lock().unwrap().clone()
+    }
+
+    /// Panics if any of the expected invocations has not been used.
+    pub fn assert_all_used(&self) {
+        let remaining = self.remaining();
+        assert!(
+            remaining.is_empty(),
+            "expected invocations were never run: {:#?}",
+            remaining
+        );
+    }
+
+    fn take(&self, command: &ShellCommand) -> Invocation {
+        let mut expected = self.expected.lock().unwrap();
+        let Some(index) = expected
+            .iter()
+            .position(|invocation| invocation.matches(command))
+        else {
+            let remaining = expected.clone();
+            drop(expected);
+            panic!(
+                "unexpected invocation of '{} {}' in {:?} (env: {:?}, stdin: {:?}), remaining \
+                 invocations: {:#?}",
+                command.program,
+                command.args.join(" "),
+                command.cwd,
+                Invocation::env_of(command),
+                Invocation::stdin_of(command),
+                remaining
+            );
+        };
+
+        expected.remove(index)
+    }
+}
+
+impl Shell for ScriptedShell {
+    fn execute(
+        &self,
+        command: &ShellCommand,
+        on_line: &mut dyn FnMut(OutputLine),
+    ) -> Result<ShellOutput, ShellError> {
+        let mut invocation = self.take(command);
+        if let Some((stdout, stderr)) = invocation.result.output_mut() {
+            stdout
+                .lines()
+                .for_each(|line| on_line(OutputLine::Stdout(line.to_string())));
+            stderr
+                .lines()
+                .for_each(|line| on_line(OutputLine::Stderr(line.to_string())));
+        }
+
+        invocation.replay()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{exec_in, exec_on};
+
+    #[test]
+    fn replays_expected_invocations_in_any_order() {
+        let shell = ScriptedShell::new()
+            .expect(Invocation::new("git", ["status"]).stdout("clean\n"))
+            .expect(
+                Invocation::new("flutter", ["test"])
+                    .in_dir("/project")
+                    .stderr("1 test failed\n")
+                    .exit_code(1),
+            );
+
+        let error = exec_in!(shell, Path::new("/project"), "flutter", "test").unwrap_err();
+        let ShellError::HostProcessExecutionFailure { status, stderr, .. } = error else {
+            panic!("expected a HostProcessExecutionFailure, got {:?}", error);
+        };
+        assert_eq!(status.code(), Some(1));
+        assert_eq!(stderr, "1 test failed\n");
+
+        assert_eq!(
+            exec_on!(shell, "git", "status")
+                .unwrap()
+                .stdout_utf8()
+                .unwrap(),
+            "clean\n"
+        );
+        shell.assert_all_used();
+    }
+
+    #[test]
+    fn replays_start_failures() {
+        let shell =
+            ScriptedShell::new().expect(Invocation::new("open", ["x"]).start_failure("nope"));
+
+        assert!(matches!(
+            exec_on!(shell, "open", "x"),
+            Err(ShellError::ShellStartFailure { error, .. }) if error == "nope"
+        ));
+    }
+
+    #[test]
+    fn streams_replayed_output() {
+        let shell = ScriptedShell::new().expect(
+            Invocation::new("dart", ["test"])
+                .stdout("a\nb\n")
+                .stderr("c\n"),
+        );
+
+        let mut lines = vec![];
+        let output = ShellCommand::new("dart")
+            .arg("test")
+            .run_streaming(&shell, &mut |line| lines.push(line))
+            .unwrap();
+
+        assert_eq!(output.stdout_utf8().unwrap(), "a\nb\n");
+        assert_eq!(
+            lines,
+            vec![
+                OutputLine::Stdout("a".to_string()),
+                OutputLine::Stdout("b".to_string()),
+                OutputLine::Stderr("c".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_environment_and_stdin() {
+        let shell = ScriptedShell::new().expect(
+            Invocation::new("dart", ["pub", "get"])
+                .env("PUB_CACHE", "/cache")
+                .env_remove("FLUTTER_ROOT")
+                .stdin("y\n"),
+        );
+
+        ShellCommand::new("dart")
+            .args(["pub", "get"])
+            .env("PUB_CACHE", "/tmp")
+            .env("PUB_CACHE", "/cache")
+            .env_remove("FLUTTER_ROOT")
+            .stdin("y\n")
+            .run(&shell)
+            .unwrap();
+        shell.assert_all_used();
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected invocation of 'dart pub get'")]
+    fn panics_on_missing_environment_variables() {
+        let shell = ScriptedShell::new()
+            .expect(Invocation::new("dart", ["pub", "get"]).env("PUB_CACHE", "/cache"));
+        let _ = exec_on!(shell, "dart", "pub", "get");
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected invocation of 'git push'")]
+    fn panics_on_unexpected_invocations() {
+        let shell = ScriptedShell::new().expect(Invocation::new("git", ["status"]));
+        let _ = exec_on!(shell, "git", "push");
+    }
+}
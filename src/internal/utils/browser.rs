@@ -0,0 +1,103 @@
+use crate::internal::shell::Shell;
+use log::debug;
+use std::env;
+use thiserror::Error;
+
+/// The commands that are tried (in order) to open URLs when no browser is configured and
+/// `$BROWSER` does not work: `xdg-open` (Linux), `wslview` (WSL) and `open` (macOS).
+pub const DEFAULT_OPENERS: [&str; 3] = ["xdg-open", "wslview", "open"];
+
+#[derive(Error, Debug)]
+pub enum BrowserError {
+    #[error("could not open '{url}' in a browser (tried {}); set `browser` in suitcase.yaml or use --print to only print the url", .attempts.join(", "))]
+    NoBrowserAvailable { url: String, attempts: Vec<String> },
+}
+
+/// A command that opens URLs, such as `xdg-open` or `firefox --new-tab %s`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrowserCommand {
+    command: String,
+    args: Vec<String>,
+}
+
+impl BrowserCommand {
+    /// Parses a command line, which is split on whitespace. The URL replaces any `%s` argument,
+    /// or is appended if there is none.
+    pub fn parse(command_line: &str) -> Option<Self> {
+        let mut words = command_line.split_whitespace().map(str::to_string);
+
+        Some(Self {
+            command: words.next()?,
+            args: words.collect(),
+        })
+    }
+
+    /// Returns the command and args that open the given URL.
+    fn invocation(&self, url: &str) -> (String, Vec<String>) {
+        let mut args: Vec<String> = self.args.iter().map(|arg| arg.replace("%s", url)).collect();
+        if !self.args.iter().any(|arg| arg.contains("%s")) {
+            args.push(url.to_string());
+        }
+
+        (self.command.clone(), args)
+    }
+}
+
+/// Opens URLs in the user's browser by trying a list of browser commands until one succeeds.
+pub struct BrowserLauncher {
+    candidates: Vec<BrowserCommand>,
+}
+
+impl BrowserLauncher {
+    /// Creates a launcher that tries the given custom command (if any), then every command in
+    /// `$BROWSER` (which may contain multiple commands separated by `:`), then the
+    /// `DEFAULT_OPENERS`.
+    pub fn new(custom_command: Option<&str>) -> Self {
+        let mut candidates: Vec<BrowserCommand> = custom_command
+            .and_then(BrowserCommand::parse)
+            .into_iter()
+            .collect();
+
+        if let Ok(browser) = env::var("BROWSER") {
+            candidates.extend(browser.split(':').filter_map(BrowserCommand::parse));
+        }
+
+        candidates.extend(
+            DEFAULT_OPENERS
+                .iter()
+                .filter_map(|opener| BrowserCommand::parse(opener)),
+        );
+
+        // `cmd /C start` would split URLs at `&` (like in query strings), so the URL is passed to
+        // the default URL handler directly.
+        if cfg!(windows) {
+            candidates.push(BrowserCommand {
+                command: "rundll32".to_string(),
+                args: vec!["url.dll,FileProtocolHandler".to_string(), "%s".to_string()],
+            });
+        }
+
+        Self { candidates }
+    }
+
+    /// Opens the given URL with the first browser command that succeeds.
+    pub fn open(&self, shell: &dyn Shell, url: &str) -> Result<(), BrowserError> {
+        let mut attempts = vec![];
+
+        for candidate in &self.candidates {
+            let (command, args) = candidate.invocation(url);
+            match shell.run_command(command.clone(), args) {
+                Ok(_) => return Ok(()),
+                Err(err) => {
+                    debug!("failed to open url with '{}': {}", command, err);
+                    attempts.push(command);
+                }
+            }
+        }
+
+        Err(BrowserError::NoBrowserAvailable {
+            url: url.to_string(),
+            attempts,
+        })
+    }
+}
@@ -1,9 +1,11 @@
-use crate::{exec_on, internal::shell::Shell};
+use super::pubspec::Pubspec;
 use anyhow::Context;
+use ignore::WalkBuilder;
 use log::debug;
-use std::{env, path::PathBuf};
+use std::{collections::BTreeSet, env, path::PathBuf};
 
-const IGNORED_FOLDERS: [&str; 12] = [
+/// Folders that are never searched for Dart projects (unless configured otherwise).
+pub const IGNORED_FOLDERS: [&str; 12] = [
     "ios",
     "android",
     "windows",
@@ -18,64 +20,91 @@ const IGNORED_FOLDERS: [&str; 12] = [
     "flutter_gen",
 ];
 
-pub struct DartShell<'a> {
-    shell: &'a Shell,
+/// Folders that are never searched for Dart projects, regardless of the configuration, since
+/// walking them (like the object store of a Git repository) can be expensive.
+const ALWAYS_IGNORED_FOLDERS: [&str; 1] = [".git"];
+
+pub struct DartShell {
+    ignored_folders: Vec<String>,
 }
 
-impl<'a> DartShell<'a> {
-    pub fn new(shell: &'a Shell) -> Self {
-        Self { shell }
+impl DartShell {
+    /// Creates a new `DartShell` that never searches the given folders for Dart projects.
+    pub fn new(ignored_folders: Vec<String>) -> Self {
+        Self { ignored_folders }
     }
 
+    /// Finds all Dart projects (directories containing a `pubspec.yaml` file) in the given path
+    /// (or the current directory) and its subdirectories.
+    ///
+    /// Ignored folders (see `DartShell::new`), `.git` folders and anything matched by
+    /// `.gitignore` files are skipped without descending into them. Symbolic links are followed, but links that point
+    /// back to one of their ancestors are skipped. When `max_depth` is given, only directories at
+    /// most that many levels below `path` are searched.
     pub fn find_dart_projects(
         &self,
         path: Option<&PathBuf>,
+        max_depth: Option<usize>,
     ) -> anyhow::Result<Vec<DartProjectMetadata>> {
         let cwd = env::current_dir().context("trying to get current directory")?;
         let path = path.unwrap_or(&cwd);
         debug!("finding Dart projects recursively in path: {:?}", path);
 
-        let path_str = path.to_str().context(format!(
-            "trying to convert path '{}' to a string",
-            path.display()
-        ))?;
-
-        let output = exec_on!(
-            self.shell,
-            "find",
-            path_str,
-            "-type",
-            "f",
-            "-name",
-            "pubspec.yaml",
-            "-exec",
-            "dirname",
-            "{}",
-            ";"
-        )
-        .context(format!(
-            "trying to find Dart projects in path '{}'",
-            path.display()
-        ))?;
-
-        let projects = output
-            .stdout
-            .lines()
-            .map(|line| PathBuf::try_from(line.trim()))
-            .filter_map(Result::ok)
-            .map(|path| path.canonicalize())
-            .filter_map(Result::ok)
-            .filter(|path| {
-                let Some(last_component) = path.components().last() else {
-                    return true;
-                };
-
-                let Some(dir_name) = last_component.as_os_str().to_str() else {
-                    return true;
-                };
-
-                !IGNORED_FOLDERS.contains(&dir_name)
+        let ignored_folders = self.ignored_folders.clone();
+        let walker = WalkBuilder::new(path)
+            .hidden(false)
+            .follow_links(true)
+            .require_git(false)
+            // Pubspec files are at most one level deeper than the deepest directory to search.
+            .max_depth(max_depth.map(|max_depth| max_depth + 1))
+            .filter_entry(move |entry| {
+                let is_dir = entry
+                    .file_type()
+                    .is_some_and(|file_type| file_type.is_dir());
+                let is_ignored = entry.file_name().to_str().is_some_and(|name| {
+                    ALWAYS_IGNORED_FOLDERS.contains(&name)
+                        || ignored_folders.iter().any(|folder| folder == name)
+                });
+
+                entry.depth() == 0 || !(is_dir && is_ignored)
             })
+            .build();
+
+        let mut project_paths = BTreeSet::new();
+        for entry in walker {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    debug!("skipping entry while finding Dart projects: {}", err);
+                    continue;
+                }
+            };
+
+            let is_pubspec = entry.file_name() == "pubspec.yaml"
+                && entry
+                    .file_type()
+                    .is_some_and(|file_type| file_type.is_file());
+            if !is_pubspec {
+                continue;
+            }
+
+            let Some(project_path) = entry.path().parent() else {
+                continue;
+            };
+
+            match project_path.canonicalize() {
+                Ok(project_path) => {
+                    project_paths.insert(project_path);
+                }
+                Err(err) => debug!(
+                    "skipping project path {:?} that could not be resolved: {}",
+                    project_path, err
+                ),
+            }
+        }
+
+        let projects = project_paths
+            .into_iter()
             .map(|path| self.get_dart_project_metadata(path))
             .collect::<Result<Vec<_>, _>>()?;
 
@@ -84,45 +113,113 @@ impl<'a> DartShell<'a> {
         Ok(projects)
     }
 
+    /// Reads the metadata of the Dart project at the given path by parsing its `pubspec.yaml`.
     pub fn get_dart_project_metadata(&self, path: PathBuf) -> anyhow::Result<DartProjectMetadata> {
-        let name = path
-            .file_name()
-            .context(format!(
-                "trying to get file name from path '{}'",
-                path.display()
-            ))?
-            .to_str()
-            .context(format!(
-                "trying to convert file name from path '{}' to a string",
-                path.display()
-            ))?
-            .to_string();
-
         let pubspec_file_path = path.join("pubspec.yaml");
 
         let pubspec_file = std::fs::File::open(&pubspec_file_path).context(format!(
             "trying to open pubspec.yaml file at path '{}'",
             pubspec_file_path.display()
         ))?;
-        let pubspec_yaml: serde_yaml::Value =
-            serde_yaml::from_reader(pubspec_file).context(format!(
-                "trying to parse pubspec.yaml file at path '{}'",
-                pubspec_file_path.display()
-            ))?;
+        let pubspec: Pubspec = serde_yaml::from_reader(pubspec_file).context(format!(
+            "trying to parse pubspec.yaml file at path '{}'",
+            pubspec_file_path.display()
+        ))?;
 
-        let is_flutter_project = !pubspec_yaml["dependencies"]["flutter"].is_null();
+        // Fall back to the folder name for (invalid) pubspecs that do not specify a name.
+        let name = match &pubspec.name {
+            Some(name) => name.clone(),
+            None => path
+                .file_name()
+                .context(format!(
+                    "trying to get file name from path '{}'",
+                    path.display()
+                ))?
+                .to_string_lossy()
+                .to_string(),
+        };
+
+        let is_flutter_project = pubspec.is_flutter_package();
 
         Ok(DartProjectMetadata {
             path,
             name,
             is_flutter_project,
+            pubspec,
         })
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DartProjectMetadata {
     pub path: PathBuf,
     pub name: String,
     pub is_flutter_project: bool,
+    pub pubspec: Pubspec,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, path::Path};
+
+    fn create_project(root: &Path, folder: &str) {
+        let path = root.join(folder);
+        fs::create_dir_all(&path).unwrap();
+        let name = path.file_name().unwrap().to_str().unwrap().to_string();
+        fs::write(path.join("pubspec.yaml"), format!("name: {}\n", name)).unwrap();
+    }
+
+    fn find(root: &Path, ignored_folders: &[&str], max_depth: Option<usize>) -> Vec<String> {
+        let shell = DartShell::new(ignored_folders.iter().map(|f| f.to_string()).collect());
+        shell
+            .find_dart_projects(Some(&root.to_path_buf()), max_depth)
+            .unwrap()
+            .into_iter()
+            .map(|project| project.name)
+            .collect()
+    }
+
+    #[test]
+    fn skips_ignored_folders_without_descending_into_them() {
+        let root = tempfile::tempdir().unwrap();
+        create_project(root.path(), "app");
+        create_project(root.path(), "app/build/generated");
+        create_project(root.path(), "app/ios/Pods/nested");
+        create_project(root.path(), "vendor/third_party");
+        create_project(root.path(), ".git/modules/vendored");
+
+        let ignored_folders = IGNORED_FOLDERS.to_vec();
+        assert_eq!(
+            find(root.path(), &ignored_folders, None),
+            ["app", "third_party"]
+        );
+        assert_eq!(
+            find(root.path(), &["vendor"], None),
+            ["app", "generated", "nested"]
+        );
+    }
+
+    #[test]
+    fn skips_packages_ignored_by_gitignore() {
+        let root = tempfile::tempdir().unwrap();
+        create_project(root.path(), "app");
+        create_project(root.path(), "scratch/experiment");
+        fs::write(root.path().join(".gitignore"), "scratch/\n").unwrap();
+
+        assert_eq!(find(root.path(), &[], None), ["app"]);
+    }
+
+    #[test]
+    fn only_searches_up_to_max_depth() {
+        let root = tempfile::tempdir().unwrap();
+        create_project(root.path(), "");
+        create_project(root.path(), "app");
+        create_project(root.path(), "packages/core");
+
+        let root_name = root.path().file_name().unwrap().to_str().unwrap();
+        assert_eq!(find(root.path(), &[], Some(0)), [root_name]);
+        assert_eq!(find(root.path(), &[], Some(1)), [root_name, "app"]);
+        assert_eq!(find(root.path(), &[], None), [root_name, "app", "core"]);
+    }
 }
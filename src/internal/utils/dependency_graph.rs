@@ -0,0 +1,291 @@
+use super::{dart::DartProjectMetadata, pubspec::DependencySource};
+use std::collections::BTreeSet;
+use thiserror::Error;
+
+/// A graph of the `path:` dependencies between a set of local Dart projects.
+///
+/// Nodes are referred to by their index in the slice of projects the graph was built from.
+pub struct DependencyGraph<'a> {
+    projects: &'a [DartProjectMetadata],
+    /// For every project, the indices of the local projects it depends on.
+    dependencies: Vec<BTreeSet<usize>>,
+}
+
+#[derive(Error, Debug)]
+pub enum DependencyGraphError {
+    #[error("found dependency cycles between local packages: {}", format_cycles(.cycles))]
+    CyclicDependencies { cycles: Vec<Vec<String>> },
+}
+
+impl<'a> DependencyGraph<'a> {
+    /// Builds a dependency graph from the `path:` dependencies (including dev dependencies and
+    /// dependency overrides) of the given projects.
+    ///
+    /// Dependencies on paths that do not point to one of the given projects are ignored.
+    pub fn new(projects: &'a [DartProjectMetadata]) -> Self {
+        let dependencies = projects
+            .iter()
+            .map(|project| {
+                project
+                    .pubspec
+                    .all_dependencies()
+                    .filter_map(|(_, dependency)| match &dependency.source {
+                        DependencySource::Path { path } => {
+                            project.path.join(path).canonicalize().ok()
+                        }
+                        _ => None,
+                    })
+                    .filter_map(|dependency_path| {
+                        projects
+                            .iter()
+                            .position(|other| other.path == dependency_path)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            projects,
+            dependencies,
+        }
+    }
+
+    /// Returns the projects this graph was built from.
+    pub fn projects(&self) -> &'a [DartProjectMetadata] {
+        self.projects
+    }
+
+    /// Returns the indices of the local projects the project at the given index depends on.
+    pub fn dependencies_of(&self, index: usize) -> &BTreeSet<usize> {
+        &self.dependencies[index]
+    }
+
+    /// Returns the project indices grouped into levels in topological order.
+    ///
+    /// Every project only depends on projects in earlier levels, so all projects within a single
+    /// level can safely be processed concurrently. Returns an error listing every cycle if the
+    /// graph is not acyclic.
+    pub fn levels(&self) -> Result<Vec<Vec<usize>>, DependencyGraphError> {
+        let cycles = self.cycles();
+        if !cycles.is_empty() {
+            return Err(DependencyGraphError::CyclicDependencies {
+                cycles: cycles
+                    .into_iter()
+                    .map(|cycle| {
+                        cycle
+                            .into_iter()
+                            .map(|index| self.projects[index].name.clone())
+                            .collect()
+                    })
+                    .collect(),
+            });
+        }
+
+        let mut remaining: BTreeSet<usize> = (0..self.projects.len()).collect();
+        let mut levels = vec![];
+
+        while !remaining.is_empty() {
+            let level: Vec<usize> = remaining
+                .iter()
+                .copied()
+                .filter(|index| self.dependencies[*index].is_disjoint(&remaining))
+                .collect();
+
+            for index in &level {
+                remaining.remove(index);
+            }
+            levels.push(level);
+        }
+
+        Ok(levels)
+    }
+
+    /// Returns every cycle in the graph as a list of project indices.
+    ///
+    /// Each cycle is a strongly connected component with more than one project, or a single
+    /// project that depends on itself.
+    pub fn cycles(&self) -> Vec<Vec<usize>> {
+        let mut tarjan = Tarjan {
+            graph: self,
+            next_index: 0,
+            indices: vec![None; self.projects.len()],
+            low_links: vec![0; self.projects.len()],
+            stack: vec![],
+            on_stack: vec![false; self.projects.len()],
+            components: vec![],
+        };
+
+        for index in 0..self.projects.len() {
+            if tarjan.indices[index].is_none() {
+                tarjan.visit(index);
+            }
+        }
+
+        tarjan
+            .components
+            .into_iter()
+            .filter(|component| {
+                component.len() > 1 || self.dependencies[component[0]].contains(&component[0])
+            })
+            .map(|mut component| {
+                component.sort();
+                component
+            })
+            .collect()
+    }
+}
+
+/// State for Tarjan's strongly connected components algorithm.
+struct Tarjan<'g, 'a> {
+    graph: &'g DependencyGraph<'a>,
+    next_index: usize,
+    indices: Vec<Option<usize>>,
+    low_links: Vec<usize>,
+    stack: Vec<usize>,
+    on_stack: Vec<bool>,
+    components: Vec<Vec<usize>>,
+}
+
+impl Tarjan<'_, '_> {
+    fn visit(&mut self, node: usize) {
+        self.indices[node] = Some(self.next_index);
+        self.low_links[node] = self.next_index;
+        self.next_index += 1;
+        self.stack.push(node);
+        self.on_stack[node] = true;
+
+        for &dependency in self.graph.dependencies_of(node) {
+            match self.indices[dependency] {
+                None => {
+                    self.visit(dependency);
+                    self.low_links[node] = self.low_links[node].min(self.low_links[dependency]);
+                }
+                Some(index) if self.on_stack[dependency] => {
+                    self.low_links[node] = self.low_links[node].min(index);
+                }
+                Some(_) => {}
+            }
+        }
+
+        if Some(self.low_links[node]) == self.indices[node] {
+            let mut component = vec![];
+            while let Some(member) = self.stack.pop() {
+                self.on_stack[member] = false;
+                component.push(member);
+                if member == node {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+}
+
+fn format_cycles(cycles: &[Vec<String>]) -> String {
+    cycles
+        .iter()
+        .map(|cycle| format!("[{}]", cycle.join(", ")))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::utils::dart::DartShell;
+    use std::{fs, path::Path};
+
+    /// Creates a project for every `(name, dependencies)` pair in a folder of the same name, with
+    /// `path:` dependencies on the given projects, and returns the projects sorted by name.
+    fn create_projects(root: &Path, projects: &[(&str, &[&str])]) -> Vec<DartProjectMetadata> {
+        for (name, dependencies) in projects {
+            let path = root.join(name);
+            fs::create_dir_all(&path).unwrap();
+            let dependencies: String = dependencies
+                .iter()
+                .map(|dependency| format!("  {}:\n    path: ../{}\n", dependency, dependency))
+                .collect();
+            fs::write(
+                path.join("pubspec.yaml"),
+                format!("name: {}\ndependencies:\n{}", name, dependencies),
+            )
+            .unwrap();
+        }
+
+        let mut projects = DartShell::new(vec![])
+            .find_dart_projects(Some(&root.to_path_buf()), None)
+            .unwrap();
+        projects.sort_by(|a, b| a.name.cmp(&b.name));
+        projects
+    }
+
+    fn names(projects: &[DartProjectMetadata], indices: &[usize]) -> Vec<String> {
+        indices
+            .iter()
+            .map(|index| projects[*index].name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn groups_a_diamond_into_levels() {
+        let root = tempfile::tempdir().unwrap();
+        let projects = create_projects(
+            root.path(),
+            &[
+                ("app", &["auth", "ui"]),
+                ("auth", &["core"]),
+                ("core", &[]),
+                ("ui", &["core"]),
+            ],
+        );
+        let graph = DependencyGraph::new(&projects);
+
+        assert!(graph.cycles().is_empty());
+        let levels: Vec<Vec<String>> = graph
+            .levels()
+            .unwrap()
+            .iter()
+            .map(|level| names(&projects, level))
+            .collect();
+        assert_eq!(levels, [vec!["core"], vec!["auth", "ui"], vec!["app"]]);
+    }
+
+    #[test]
+    fn detects_cycles_between_projects() {
+        let root = tempfile::tempdir().unwrap();
+        let projects = create_projects(
+            root.path(),
+            &[
+                ("a", &["b"]),
+                ("b", &["c"]),
+                ("c", &["a"]),
+                ("d", &["a"]),
+                ("e", &[]),
+            ],
+        );
+        let graph = DependencyGraph::new(&projects);
+
+        let cycles = graph.cycles();
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(names(&projects, &cycles[0]), ["a", "b", "c"]);
+        assert_eq!(
+            graph.levels().unwrap_err().to_string(),
+            "found dependency cycles between local packages: [a, b, c]"
+        );
+    }
+
+    #[test]
+    fn detects_projects_that_depend_on_themselves() {
+        let root = tempfile::tempdir().unwrap();
+        let projects = create_projects(root.path(), &[("a", &["a"]), ("b", &["a"])]);
+        let graph = DependencyGraph::new(&projects);
+
+        let cycles = graph.cycles();
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(names(&projects, &cycles[0]), ["a"]);
+        assert!(matches!(
+            graph.levels(),
+            Err(DependencyGraphError::CyclicDependencies { cycles }) if cycles == [vec!["a"]]
+        ));
+    }
+}
@@ -0,0 +1,185 @@
+use anyhow::Context;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+/// The FVM config file of a project (FVM 3 and later).
+pub const FVMRC_FILE: &str = ".fvmrc";
+
+/// The FVM config file of a project used by versions of FVM before 3, relative to the project.
+pub const LEGACY_FVM_CONFIG_FILE: &str = ".fvm/fvm_config.json";
+
+/// The link to the Flutter SDK that `fvm use` creates in a project, relative to the project. It is
+/// usually ignored by Git, so it is missing in fresh clones.
+pub const FVM_SDK_LINK: &str = ".fvm/flutter_sdk";
+
+/// The Flutter channels a project can be pinned to instead of a specific release.
+const FLUTTER_CHANNELS: [&str; 5] = ["stable", "beta", "dev", "master", "main"];
+
+/// A Flutter version that a project can be pinned to using FVM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FlutterVersion {
+    /// A Flutter channel (like `stable`), which always uses the latest release of the channel.
+    Channel(String),
+    /// Any other version FVM supports, like a release (`3.10.0`) or a commit.
+    Release(String),
+}
+
+impl FlutterVersion {
+    /// Parses the given version, where channel names are case-insensitive.
+    pub fn parse(version: &str) -> anyhow::Result<Self> {
+        let version = version.trim();
+        if version.is_empty() {
+            anyhow::bail!("the Flutter version cannot be empty");
+        }
+
+        let channel = version.to_lowercase();
+        Ok(if FLUTTER_CHANNELS.contains(&channel.as_str()) {
+            Self::Channel(channel)
+        } else {
+            Self::Release(version.to_string())
+        })
+    }
+
+    /// The version as passed to FVM.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Channel(channel) => channel,
+            Self::Release(release) => release,
+        }
+    }
+}
+
+impl fmt::Display for FlutterVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Channel(channel) => write!(f, "Flutter channel '{}'", channel),
+            Self::Release(release) => write!(f, "Flutter version '{}'", release),
+        }
+    }
+}
+
+/// The FVM config of a project.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FvmConfig {
+    /// The Flutter version the project is pinned to.
+    pub flutter: FlutterVersion,
+
+    /// The file the config was read from.
+    pub path: PathBuf,
+}
+
+/// The contents of a `.fvmrc` file (only the fields that are relevant to suitcase).
+#[derive(Deserialize)]
+struct Fvmrc {
+    flutter: Option<String>,
+}
+
+/// The contents of a legacy `.fvm/fvm_config.json` file (only the fields that are relevant to
+/// suitcase).
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LegacyFvmConfig {
+    flutter_sdk_version: Option<String>,
+}
+
+impl FvmConfig {
+    /// Reads the FVM config of the project in the given directory from its `.fvmrc` file, or from
+    /// its legacy `.fvm/fvm_config.json` file when it has no `.fvmrc` file.
+    ///
+    /// Returns `None` when the project has neither.
+    pub fn read(project_path: &Path) -> anyhow::Result<Option<Self>> {
+        let fvmrc_path = project_path.join(FVMRC_FILE);
+        let legacy_path = project_path.join(LEGACY_FVM_CONFIG_FILE);
+
+        let (path, version) = if fvmrc_path.is_file() {
+            let fvmrc: Fvmrc = read_json(&fvmrc_path)?;
+            (fvmrc_path, fvmrc.flutter)
+        } else if legacy_path.is_file() {
+            let legacy: LegacyFvmConfig = read_json(&legacy_path)?;
+            (legacy_path, legacy.flutter_sdk_version)
+        } else {
+            return Ok(None);
+        };
+
+        let version = version.context(format!(
+            "FVM config file '{}' does not specify a Flutter version",
+            path.display()
+        ))?;
+        Ok(Some(Self {
+            flutter: FlutterVersion::parse(&version).context(format!(
+                "trying to parse FVM config file '{}'",
+                path.display()
+            ))?,
+            path,
+        }))
+    }
+
+    /// Whether the project in the given directory has a link to an installed Flutter SDK (as
+    /// created by `fvm use`).
+    pub fn has_sdk_link(project_path: &Path) -> bool {
+        // `exists` follows the link, so a link to an SDK that is no longer installed is ignored.
+        project_path.join(FVM_SDK_LINK).exists()
+    }
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> anyhow::Result<T> {
+    let contents = fs::read_to_string(path).context(format!(
+        "trying to read FVM config file '{}'",
+        path.display()
+    ))?;
+    serde_json::from_str(&contents).context(format!(
+        "trying to parse FVM config file '{}'",
+        path.display()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_channels_case_insensitively() {
+        assert_eq!(
+            FlutterVersion::parse("Stable").unwrap(),
+            FlutterVersion::Channel("stable".to_string())
+        );
+        assert_eq!(
+            FlutterVersion::parse("3.10.0").unwrap(),
+            FlutterVersion::Release("3.10.0".to_string())
+        );
+        assert!(FlutterVersion::parse(" ").is_err());
+    }
+
+    #[test]
+    fn reads_fvmrc_before_legacy_config() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(FvmConfig::read(dir.path()).unwrap(), None);
+
+        fs::create_dir(dir.path().join(".fvm")).unwrap();
+        fs::write(
+            dir.path().join(LEGACY_FVM_CONFIG_FILE),
+            r#"{"flutterSdkVersion": "3.10.0", "flavors": {}}"#,
+        )
+        .unwrap();
+        let config = FvmConfig::read(dir.path()).unwrap().unwrap();
+        assert_eq!(
+            config.flutter,
+            FlutterVersion::Release("3.10.0".to_string())
+        );
+
+        fs::write(
+            dir.path().join(FVMRC_FILE),
+            r#"{"flutter": "beta", "updateVscodeSettings": true}"#,
+        )
+        .unwrap();
+        let config = FvmConfig::read(dir.path()).unwrap().unwrap();
+        assert_eq!(config.flutter, FlutterVersion::Channel("beta".to_string()));
+        assert_eq!(config.path, dir.path().join(FVMRC_FILE));
+
+        fs::write(dir.path().join(FVMRC_FILE), "{}").unwrap();
+        assert!(FvmConfig::read(dir.path()).is_err());
+    }
+}
@@ -1,2 +1,10 @@
+pub mod browser;
 pub mod dart;
-pub mod directory_utils;
+pub mod dependency_graph;
+pub mod fvm;
+pub mod git_remote;
+pub mod parallel;
+pub mod project_filter;
+pub mod pubspec;
+pub mod retry;
+pub mod table;
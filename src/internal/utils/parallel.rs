@@ -0,0 +1,101 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+/// Runs `job` for every item in `items` using a pool of at most `jobs` worker threads and returns
+/// the results in the same order as `items`.
+///
+/// Once `should_stop` returns `true` for any result, no new items are picked up by the workers
+/// (items that are already running are allowed to finish). Items that were never started have a
+/// result of `None`.
+pub fn run_in_parallel<T, R>(
+    items: &[T],
+    jobs: NonZeroUsize,
+    job: impl Fn(&T) -> R + Sync,
+    should_stop: impl Fn(&R) -> bool + Sync,
+) -> Vec<Option<R>>
+where
+    T: Sync,
+    R: Send,
+{
+    let next_index = AtomicUsize::new(0);
+    let stopped = AtomicBool::new(false);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+    let worker_count = jobs.get().min(items.len());
+
+    thread::scope(|scope| {
+        for _ in 0..worker_count {
+            scope.spawn(|| loop {
+                if stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+
+                let index = next_index.fetch_add(1, Ordering::SeqCst);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+
+                let result = job(item);
+                if should_stop(&result) {
+                    stopped.store(true, Ordering::SeqCst);
+                }
+
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+
+    results.into_inner().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn returns_results_in_the_order_of_the_items() {
+        // Earlier items take longer, so they finish after the later ones.
+        let items = [40, 30, 20, 10, 0];
+        let results = run_in_parallel(
+            &items,
+            NonZeroUsize::new(5).unwrap(),
+            |millis| {
+                thread::sleep(Duration::from_millis(*millis));
+                millis * 2
+            },
+            |_| false,
+        );
+
+        assert_eq!(results, [Some(80), Some(60), Some(40), Some(20), Some(0)]);
+    }
+
+    #[test]
+    fn stops_picking_up_items_after_an_error() {
+        let items = [1, 2, 3, 4];
+        let results = run_in_parallel(
+            &items,
+            NonZeroUsize::new(1).unwrap(),
+            |item| match item {
+                2 => Err(format!("item {} failed", item)),
+                _ => Ok(*item),
+            },
+            Result::is_err,
+        );
+
+        assert_eq!(
+            results,
+            [
+                Some(Ok(1)),
+                Some(Err("item 2 failed".to_string())),
+                None,
+                None
+            ]
+        );
+    }
+}
@@ -0,0 +1,391 @@
+use super::{dart::DartProjectMetadata, dependency_graph::DependencyGraph};
+use crate::{
+    exec_on,
+    internal::{
+        config::{Config, Sourced},
+        shell::Shell,
+    },
+};
+use anyhow::Context;
+use clap::Args;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use log::debug;
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+/// Options for selecting a subset of the Dart projects found by a command.
+#[derive(Args, Debug, Clone, Default)]
+pub struct ProjectFilterOptions {
+    /// Only include projects whose name matches the given glob (can be used multiple times).
+    #[arg(long = "include", value_name = "GLOB")]
+    pub include: Vec<String>,
+
+    /// Exclude projects whose name matches the given glob (can be used multiple times).
+    #[arg(long = "exclude", value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Only include projects whose path (relative to the search path) matches the given glob
+    /// (can be used multiple times).
+    #[arg(long = "path-glob", value_name = "GLOB")]
+    pub path_globs: Vec<String>,
+
+    /// Only include projects that depend on the given package, either directly or through other
+    /// local projects (can be used multiple times).
+    #[arg(long = "depends-on", value_name = "PACKAGE")]
+    pub depends_on: Vec<String>,
+
+    /// Only include projects that contain files that changed since the given Git ref (including
+    /// uncommitted and untracked files).
+    #[arg(long = "changed-since", value_name = "GIT_REF")]
+    pub changed_since: Option<String>,
+}
+
+impl ProjectFilterOptions {
+    /// Returns a copy of these options in which the include and exclude globs fall back to the
+    /// configured ones when none were given on the command line.
+    pub fn with_config_defaults(&self, config: &Config) -> Self {
+        let configured = |globs: &[Sourced<String>]| {
+            globs
+                .iter()
+                .map(|glob| glob.value.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let mut options = self.clone();
+        if options.include.is_empty() {
+            options.include = configured(&config.include);
+        }
+        if options.exclude.is_empty() {
+            options.exclude = configured(&config.exclude);
+        }
+
+        options
+    }
+
+    /// Returns whether any filter is set.
+    pub fn is_active(&self) -> bool {
+        !self.include.is_empty()
+            || !self.exclude.is_empty()
+            || !self.path_globs.is_empty()
+            || !self.depends_on.is_empty()
+            || self.changed_since.is_some()
+    }
+
+    /// Returns only the projects that match all filters.
+    ///
+    /// Relative paths (for `--path-glob`) are resolved against `root`, which is usually the path
+    /// the projects were searched in.
+    pub fn apply(
+        &self,
+        shell: &dyn Shell,
+        root: &Path,
+        projects: Vec<DartProjectMetadata>,
+    ) -> anyhow::Result<Vec<DartProjectMetadata>> {
+        if !self.is_active() {
+            return Ok(projects);
+        }
+
+        let include = build_glob_set(&self.include).context("trying to parse --include globs")?;
+        let exclude = build_glob_set(&self.exclude).context("trying to parse --exclude globs")?;
+        let path_globs =
+            build_glob_set(&self.path_globs).context("trying to parse --path-glob globs")?;
+
+        let dependents = self.find_dependents(&projects);
+        let changed = match &self.changed_since {
+            Some(git_ref) => Some(
+                find_changed_projects(shell, root, &projects, git_ref).context(format!(
+                    "trying to find projects that changed since '{}'",
+                    git_ref
+                ))?,
+            ),
+            None => None,
+        };
+
+        let selected = projects
+            .into_iter()
+            .filter(|project| {
+                let relative_path = project.path.strip_prefix(root).unwrap_or(&project.path);
+
+                let is_selected = (self.include.is_empty() || include.is_match(&project.name))
+                    && !exclude.is_match(&project.name)
+                    && (self.path_globs.is_empty()
+                        || path_globs.is_match(relative_path)
+                        || path_globs.is_match(&project.path))
+                    && match &dependents {
+                        Some(dependents) => dependents.contains(&project.path),
+                        None => true,
+                    }
+                    && match &changed {
+                        Some(changed) => changed.contains(&project.path),
+                        None => true,
+                    };
+
+                if !is_selected {
+                    debug!("filtered out project '{}'", project.name);
+                }
+                is_selected
+            })
+            .collect();
+
+        Ok(selected)
+    }
+
+    /// Returns the paths of all projects that depend on any of the `--depends-on` packages,
+    /// either directly or through other local projects, or `None` if no such filter is set.
+    fn find_dependents(&self, projects: &[DartProjectMetadata]) -> Option<BTreeSet<PathBuf>> {
+        if self.depends_on.is_empty() {
+            return None;
+        }
+
+        let graph = DependencyGraph::new(projects);
+        let mut dependents: BTreeSet<usize> = projects
+            .iter()
+            .enumerate()
+            .filter(|(_, project)| {
+                project
+                    .pubspec
+                    .all_dependencies()
+                    .any(|(name, _)| self.depends_on.contains(name))
+            })
+            .map(|(index, _)| index)
+            .collect();
+
+        loop {
+            let indirect_dependents: Vec<usize> = (0..projects.len())
+                .filter(|index| !dependents.contains(index))
+                .filter(|index| !graph.dependencies_of(*index).is_disjoint(&dependents))
+                .collect();
+
+            if indirect_dependents.is_empty() {
+                break;
+            }
+            dependents.extend(indirect_dependents);
+        }
+
+        Some(
+            dependents
+                .into_iter()
+                .map(|index| projects[index].path.clone())
+                .collect(),
+        )
+    }
+}
+
+fn build_glob_set(globs: &[String]) -> anyhow::Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob).context(format!("trying to parse glob '{}'", glob))?);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Returns the paths of all projects that contain files that changed since the given Git ref
+/// (according to `git diff`), or that contain untracked files.
+///
+/// Every changed file is attributed to the most deeply nested project that contains it.
+fn find_changed_projects(
+    shell: &dyn Shell,
+    root: &Path,
+    projects: &[DartProjectMetadata],
+    git_ref: &str,
+) -> anyhow::Result<BTreeSet<PathBuf>> {
+    let root_str = root.to_string_lossy();
+
+    let top_level = exec_on!(shell, "git", "-C", root_str, "rev-parse", "--show-toplevel")
+        .context("trying to find the root of the Git repository")?;
+    let top_level = PathBuf::from(top_level.stdout_utf8()?.trim())
+        .canonicalize()
+        .context("trying to resolve the root of the Git repository")?;
+
+    let changed_files = exec_on!(
+        shell,
+        "git",
+        "-C",
+        top_level.to_string_lossy(),
+        "diff",
+        "--name-only",
+        "-z",
+        git_ref
+    )
+    .context(format!("trying to list files changed since '{}'", git_ref))?;
+
+    let untracked_files = exec_on!(
+        shell,
+        "git",
+        "-C",
+        top_level.to_string_lossy(),
+        "ls-files",
+        "--others",
+        "--exclude-standard",
+        "-z"
+    )
+    .context("trying to list untracked files")?;
+
+    // The paths are separated by NUL characters (`-z`), so Git does not quote paths that contain
+    // special or non-ASCII characters.
+    let changed_projects: BTreeSet<PathBuf> = changed_files
+        .stdout_utf8()?
+        .split('\0')
+        .chain(untracked_files.stdout_utf8()?.split('\0'))
+        .filter(|file| !file.is_empty())
+        .filter_map(|file| {
+            let file = top_level.join(file);
+            projects
+                .iter()
+                .filter(|project| file.starts_with(&project.path))
+                .max_by_key(|project| project.path.components().count())
+                .map(|project| project.path.clone())
+        })
+        .collect();
+
+    debug!(
+        "projects with changes since '{}': {:?}",
+        git_ref, changed_projects
+    );
+
+    Ok(changed_projects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::{
+        shell::{Invocation, ScriptedShell},
+        utils::dart::DartShell,
+    };
+    use std::fs;
+
+    /// Creates a workspace with an app that depends on `auth` (which depends on `core`) through
+    /// `path:` dependencies, a standalone `tools` package that depends on `args`, and returns the
+    /// canonical root along with the projects in it.
+    fn create_workspace(root: &Path) -> (PathBuf, Vec<DartProjectMetadata>) {
+        let projects = [
+            ("app", "auth:\n    path: ../packages/auth\n"),
+            ("packages/auth", "core:\n    path: ../core\n"),
+            ("packages/core", "meta: ^1.9.0\n"),
+            ("tools", "args: ^2.4.0\n"),
+        ];
+        for (folder, dependencies) in projects {
+            let path = root.join(folder);
+            fs::create_dir_all(&path).unwrap();
+            let name = path.file_name().unwrap().to_str().unwrap();
+            fs::write(
+                path.join("pubspec.yaml"),
+                format!("name: {}\ndependencies:\n  {}", name, dependencies),
+            )
+            .unwrap();
+        }
+
+        let root = root.canonicalize().unwrap();
+        let projects = DartShell::new(vec![])
+            .find_dart_projects(Some(&root), None)
+            .unwrap();
+        (root, projects)
+    }
+
+    fn names(projects: &[DartProjectMetadata]) -> BTreeSet<&str> {
+        projects
+            .iter()
+            .map(|project| project.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn filters_by_name_and_path_globs() {
+        let root = tempfile::tempdir().unwrap();
+        let (root, projects) = create_workspace(root.path());
+
+        let options = ProjectFilterOptions {
+            include: vec!["a*".to_string(), "core".to_string()],
+            exclude: vec!["app".to_string()],
+            ..ProjectFilterOptions::default()
+        };
+        let selected = options
+            .apply(&ScriptedShell::new(), &root, projects.clone())
+            .unwrap();
+        assert_eq!(names(&selected), BTreeSet::from(["auth", "core"]));
+
+        let options = ProjectFilterOptions {
+            path_globs: vec!["packages/*".to_string()],
+            exclude: vec!["auth".to_string()],
+            ..ProjectFilterOptions::default()
+        };
+        let selected = options
+            .apply(&ScriptedShell::new(), &root, projects)
+            .unwrap();
+        assert_eq!(names(&selected), BTreeSet::from(["core"]));
+    }
+
+    #[test]
+    fn finds_direct_and_indirect_dependents() {
+        let root = tempfile::tempdir().unwrap();
+        let (_, projects) = create_workspace(root.path());
+
+        let dependents = |depends_on: &[&str]| {
+            let options = ProjectFilterOptions {
+                depends_on: depends_on.iter().map(|name| name.to_string()).collect(),
+                ..ProjectFilterOptions::default()
+            };
+            options.find_dependents(&projects).map(|paths| {
+                projects
+                    .iter()
+                    .filter(|project| paths.contains(&project.path))
+                    .map(|project| project.name.clone())
+                    .collect::<BTreeSet<_>>()
+            })
+        };
+
+        assert_eq!(dependents(&[]), None);
+        assert_eq!(
+            dependents(&["meta"]),
+            Some(BTreeSet::from(["app", "auth", "core"].map(String::from)))
+        );
+        assert_eq!(
+            dependents(&["auth", "args"]),
+            Some(BTreeSet::from(["app", "tools"].map(String::from)))
+        );
+        assert_eq!(dependents(&["http"]), Some(BTreeSet::new()));
+    }
+
+    #[test]
+    fn attributes_changed_files_to_the_most_deeply_nested_project() {
+        let root = tempfile::tempdir().unwrap();
+        let (root, projects) = create_workspace(root.path());
+        let root_str = root.to_str().unwrap();
+
+        let shell = ScriptedShell::new()
+            .expect(
+                Invocation::new("git", ["-C", root_str, "rev-parse", "--show-toplevel"])
+                    .stdout(&format!("{}\n", root_str)),
+            )
+            .expect(
+                Invocation::new("git", ["-C", root_str, "diff", "--name-only", "-z", "main"])
+                    .stdout("packages/core/lib/café.dart\0README.md\0"),
+            )
+            .expect(
+                Invocation::new(
+                    "git",
+                    [
+                        "-C",
+                        root_str,
+                        "ls-files",
+                        "--others",
+                        "--exclude-standard",
+                        "-z",
+                    ],
+                )
+                .stdout("tools/bin/new tool.dart\0"),
+            );
+
+        let options = ProjectFilterOptions {
+            changed_since: Some("main".to_string()),
+            ..ProjectFilterOptions::default()
+        };
+        let selected = options.apply(&shell, &root, projects).unwrap();
+        assert_eq!(names(&selected), BTreeSet::from(["core", "tools"]));
+        shell.assert_all_used();
+    }
+}
@@ -0,0 +1,315 @@
+use serde::Deserialize;
+use std::{collections::BTreeMap, path::PathBuf};
+
+/// A typed representation of a Dart project's `pubspec.yaml` file.
+///
+/// Only the fields that are relevant to suitcase are included. Unknown fields are ignored.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Pubspec {
+    /// The name of the package, if specified.
+    pub name: Option<String>,
+
+    /// The version of the package, if specified.
+    pub version: Option<String>,
+
+    /// Where the package is published to (usually `none` for private packages).
+    pub publish_to: Option<String>,
+
+    /// The SDK constraints of the package.
+    pub environment: PubspecEnvironment,
+
+    /// The regular dependencies of the package.
+    pub dependencies: BTreeMap<String, Dependency>,
+
+    /// The dev dependencies of the package.
+    pub dev_dependencies: BTreeMap<String, Dependency>,
+
+    /// The dependency overrides of the package.
+    pub dependency_overrides: BTreeMap<String, Dependency>,
+
+    /// The paths of the packages in this pub workspace (if this package is a workspace root).
+    pub workspace: Option<Vec<PathBuf>>,
+
+    /// How the dependencies of this package are resolved (e.g. `workspace`).
+    pub resolution: Option<String>,
+}
+
+impl Pubspec {
+    /// Returns whether this package depends on the Flutter SDK.
+    pub fn is_flutter_package(&self) -> bool {
+        self.dependencies.contains_key("flutter")
+    }
+
+    /// Returns all dependencies of this package (regular, dev and overrides) along with their
+    /// names, in that order.
+    pub fn all_dependencies(&self) -> impl Iterator<Item = (&String, &Dependency)> {
+        self.dependencies
+            .iter()
+            .chain(self.dev_dependencies.iter())
+            .chain(self.dependency_overrides.iter())
+    }
+}
+
+/// The `environment` section of a `pubspec.yaml` file.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PubspecEnvironment {
+    /// The Dart SDK version constraint.
+    pub sdk: Option<String>,
+
+    /// The Flutter SDK version constraint.
+    pub flutter: Option<String>,
+}
+
+/// A single dependency in a `pubspec.yaml` file.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "RawDependency")]
+pub struct Dependency {
+    /// The version constraint of the dependency, if any.
+    pub version: Option<String>,
+
+    /// Where the dependency comes from.
+    pub source: DependencySource,
+}
+
+/// The source of a dependency in a `pubspec.yaml` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DependencySource {
+    /// A package from a package repository. When `url` is `None`, the default repository
+    /// (usually pub.dev) is used.
+    Hosted {
+        name: Option<String>,
+        url: Option<String>,
+    },
+
+    /// A package from a Git repository.
+    Git {
+        url: String,
+        git_ref: Option<String>,
+        path: Option<String>,
+    },
+
+    /// A package from a path on the local file system (relative to the depending package).
+    Path { path: PathBuf },
+
+    /// A package that is provided by an SDK (e.g. `flutter`).
+    Sdk { sdk: String },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawDependency {
+    Any(()),
+    Version(String),
+    Detailed(RawDetailedDependency),
+}
+
+#[derive(Deserialize)]
+struct RawDetailedDependency {
+    version: Option<String>,
+    hosted: Option<RawHostedSource>,
+    git: Option<RawGitSource>,
+    path: Option<PathBuf>,
+    sdk: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawHostedSource {
+    Url(String),
+    Detailed {
+        name: Option<String>,
+        url: Option<String>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawGitSource {
+    Url(String),
+    Detailed {
+        url: String,
+        #[serde(rename = "ref")]
+        git_ref: Option<String>,
+        path: Option<String>,
+    },
+}
+
+impl TryFrom<RawDependency> for Dependency {
+    type Error = String;
+
+    fn try_from(raw: RawDependency) -> Result<Self, Self::Error> {
+        let hosted = |version| Dependency {
+            version,
+            source: DependencySource::Hosted {
+                name: None,
+                url: None,
+            },
+        };
+
+        let detailed = match raw {
+            RawDependency::Any(()) => return Ok(hosted(None)),
+            RawDependency::Version(version) => return Ok(hosted(Some(version))),
+            RawDependency::Detailed(detailed) => detailed,
+        };
+
+        let RawDetailedDependency {
+            version,
+            hosted,
+            git,
+            path,
+            sdk,
+        } = detailed;
+
+        let source = match (hosted, git, path, sdk) {
+            (Some(RawHostedSource::Url(url)), ..) => DependencySource::Hosted {
+                name: None,
+                url: Some(url),
+            },
+            (Some(RawHostedSource::Detailed { name, url }), ..) => {
+                DependencySource::Hosted { name, url }
+            }
+            (_, Some(RawGitSource::Url(url)), ..) => DependencySource::Git {
+                url,
+                git_ref: None,
+                path: None,
+            },
+            (_, Some(RawGitSource::Detailed { url, git_ref, path }), ..) => {
+                DependencySource::Git { url, git_ref, path }
+            }
+            (_, _, Some(path), _) => DependencySource::Path { path },
+            (_, _, _, Some(sdk)) => DependencySource::Sdk { sdk },
+            (None, None, None, None) if version.is_some() => DependencySource::Hosted {
+                name: None,
+                url: None,
+            },
+            (None, None, None, None) => {
+                return Err("dependency has no version or source".to_string())
+            }
+        };
+
+        Ok(Dependency { version, source })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(yaml: &str) -> Pubspec {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn parses_short_and_long_forms_of_every_source() {
+        let pubspec = parse(
+            r#"
+name: app
+environment:
+  sdk: ^3.4.0
+dependencies:
+  any_version:
+  http: ^1.0.0
+  private:
+    hosted: https://pub.example.com
+    version: ^2.0.0
+  renamed:
+    hosted:
+      name: original
+      url: https://pub.example.com
+  short_git:
+    git: https://github.com/example/short.git
+  long_git:
+    git:
+      url: https://github.com/example/long.git
+      ref: main
+      path: packages/long
+  core:
+    path: ../core
+  flutter:
+    sdk: flutter
+"#,
+        );
+
+        let source = |name: &str| pubspec.dependencies[name].source.clone();
+        assert_eq!(pubspec.name.as_deref(), Some("app"));
+        assert_eq!(pubspec.environment.sdk.as_deref(), Some("^3.4.0"));
+        assert!(pubspec.is_flutter_package());
+
+        let hosted = |name: Option<&str>, url: Option<&str>| DependencySource::Hosted {
+            name: name.map(str::to_string),
+            url: url.map(str::to_string),
+        };
+        assert_eq!(source("any_version"), hosted(None, None));
+        assert_eq!(pubspec.dependencies["any_version"].version, None);
+        assert_eq!(source("http"), hosted(None, None));
+        assert_eq!(
+            pubspec.dependencies["http"].version.as_deref(),
+            Some("^1.0.0")
+        );
+        assert_eq!(
+            source("private"),
+            hosted(None, Some("https://pub.example.com"))
+        );
+        assert_eq!(
+            pubspec.dependencies["private"].version.as_deref(),
+            Some("^2.0.0")
+        );
+        assert_eq!(
+            source("renamed"),
+            hosted(Some("original"), Some("https://pub.example.com"))
+        );
+        assert_eq!(
+            source("short_git"),
+            DependencySource::Git {
+                url: "https://github.com/example/short.git".to_string(),
+                git_ref: None,
+                path: None,
+            }
+        );
+        assert_eq!(
+            source("long_git"),
+            DependencySource::Git {
+                url: "https://github.com/example/long.git".to_string(),
+                git_ref: Some("main".to_string()),
+                path: Some("packages/long".to_string()),
+            }
+        );
+        assert_eq!(
+            source("core"),
+            DependencySource::Path {
+                path: PathBuf::from("../core")
+            }
+        );
+        assert_eq!(
+            source("flutter"),
+            DependencySource::Sdk {
+                sdk: "flutter".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn only_packages_that_depend_on_flutter_are_flutter_packages() {
+        let pubspec = parse("name: core\nenvironment:\n  sdk: ^3.4.0\n  flutter: \">=3.22.0\"\n");
+        assert!(!pubspec.is_flutter_package());
+
+        let pubspec = parse("name: ui\ndependencies:\n  flutter:\n    sdk: flutter\n");
+        assert!(pubspec.is_flutter_package());
+    }
+
+    #[test]
+    fn rejects_malformed_dependencies() {
+        let parse_dependency = |yaml: &str| serde_yaml::from_str::<Dependency>(yaml);
+
+        let error = parse_dependency("{}").unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("dependency has no version or source"));
+        assert!(parse_dependency("[1, 2]").is_err());
+        assert!(parse_dependency("git: {ref: main}").is_err());
+        assert!(parse_dependency("path: [a, b]").is_err());
+        assert!(serde_yaml::from_str::<Pubspec>("dependencies:\n  broken: {}\n").is_err());
+    }
+}
@@ -0,0 +1,203 @@
+use crate::internal::shell::{is_interrupted, ShellError};
+use clap::Args;
+use log::info;
+use regex::Regex;
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The longest time to wait before retrying a command, no matter how many attempts failed.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// How often a retry delay checks whether Ctrl-C was pressed.
+const INTERRUPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Options for retrying commands that fail intermittently (like network-bound commands).
+#[derive(Args, Debug, Clone, Default)]
+pub struct RetryOptions {
+    /// Retry a failed (or timed out) command up to this many times, waiting twice as long before
+    /// every next retry.
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub retries: u32,
+
+    /// How long to wait before the first retry (like `500ms` or `2s`).
+    #[arg(
+        long,
+        value_name = "DURATION",
+        default_value = "1s",
+        value_parser = humantime::parse_duration
+    )]
+    pub retry_delay: Duration,
+
+    /// Only retry commands whose stderr matches the given regular expression.
+    #[arg(long, value_name = "REGEX")]
+    pub retry_on: Option<Regex>,
+}
+
+impl RetryOptions {
+    /// The maximum number of times a command is run.
+    pub fn max_attempts(&self) -> u32 {
+        self.retries.saturating_add(1)
+    }
+
+    /// Returns a suffix for progress messages describing the given (1-based) attempt, which is
+    /// empty for the first attempt.
+    pub fn describe_attempt(&self, attempt: u32) -> String {
+        if attempt <= 1 {
+            String::new()
+        } else {
+            format!(" (attempt {}/{})", attempt, self.max_attempts())
+        }
+    }
+
+    /// Returns whether a command that failed with the given error should be retried.
+    ///
+    /// Only commands that exited with a non-zero status or timed out are retried (commands that
+    /// could not be started or were interrupted are not), and only if their stderr matches
+    /// `retry_on` when it is set.
+    pub fn should_retry(&self, error: &ShellError) -> bool {
+        let stderr = match error {
+            ShellError::HostProcessExecutionFailure { stderr, .. }
+            | ShellError::Timeout { stderr, .. } => stderr,
+            ShellError::ShellStartFailure { .. }
+            | ShellError::Interrupted { .. }
+            | ShellError::InvalidOutputEncoding { .. } => return false,
+        };
+
+        match &self.retry_on {
+            Some(retry_on) => retry_on.is_match(stderr),
+            None => true,
+        }
+    }
+
+    /// Returns how long to wait before the given retry (where the first retry is attempt 2).
+    pub fn delay_before(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(2));
+        self.retry_delay.saturating_mul(factor).min(MAX_RETRY_DELAY)
+    }
+
+    /// Runs `attempt` (which receives the 1-based attempt number) until it succeeds, fails with
+    /// an error that should not be retried or runs out of attempts, and returns its last result
+    /// along with the number of attempts.
+    ///
+    /// Every retry is logged using the given description of what is being run. Retrying stops
+    /// when Ctrl-C is pressed while waiting.
+    pub fn run<T>(
+        &self,
+        description: &str,
+        mut attempt: impl FnMut(u32) -> Result<T, ShellError>,
+    ) -> (Result<T, ShellError>, u32) {
+        let mut attempts = 1;
+        loop {
+            let result = attempt(attempts);
+            let Err(error) = &result else {
+                return (result, attempts);
+            };
+            if attempts >= self.max_attempts() || !self.should_retry(error) {
+                return (result, attempts);
+            }
+
+            attempts += 1;
+            let delay = self.delay_before(attempts);
+            info!(
+                "Retrying {} in {}{}...",
+                description,
+                humantime::format_duration(delay),
+                self.describe_attempt(attempts)
+            );
+            if !Self::sleep_unless_interrupted(delay) {
+                return (result, attempts - 1);
+            }
+        }
+    }
+
+    /// Sleeps for the given duration and returns `true`, or returns `false` as soon as Ctrl-C is
+    /// pressed.
+    fn sleep_unless_interrupted(duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        loop {
+            if is_interrupted() {
+                return false;
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return true;
+            }
+            thread::sleep(INTERRUPT_POLL_INTERVAL.min(deadline - now));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failure(stderr: &str) -> ShellError {
+        ShellError::HostProcessExecutionFailure {
+            command: "bash".to_string(),
+            args: "-c dart pub get".to_string(),
+            status: std::process::ExitStatus::default(),
+            stdout: String::new(),
+            stderr: stderr.to_string(),
+        }
+    }
+
+    #[test]
+    fn retries_until_the_command_succeeds() {
+        let options = RetryOptions {
+            retries: 3,
+            ..RetryOptions::default()
+        };
+
+        let (result, attempts) = options.run("flaky command", |attempt| {
+            if attempt < 3 {
+                Err(failure("Connection reset by peer"))
+            } else {
+                Ok(attempt)
+            }
+        });
+
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn only_retries_errors_matching_retry_on() {
+        let options = RetryOptions {
+            retries: 3,
+            retry_on: Some(Regex::new("(?i)connection|timed out").unwrap()),
+            ..RetryOptions::default()
+        };
+
+        let (result, attempts) =
+            options.run("failing command", |_| Err::<(), _>(failure("Syntax error")));
+
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+        assert!(options.should_retry(&failure("Connection refused")));
+        assert!(!options.should_retry(&ShellError::Interrupted {
+            command: "bash".to_string(),
+            args: String::new(),
+            stdout: String::new(),
+            stderr: "connection".to_string(),
+        }));
+    }
+
+    #[test]
+    fn doubles_the_delay_before_every_retry() {
+        let options = RetryOptions {
+            retries: 10,
+            retry_delay: Duration::from_secs(1),
+            retry_on: None,
+        };
+
+        assert_eq!(options.delay_before(2), Duration::from_secs(1));
+        assert_eq!(options.delay_before(3), Duration::from_secs(2));
+        assert_eq!(options.delay_before(5), Duration::from_secs(8));
+        assert_eq!(options.delay_before(10), MAX_RETRY_DELAY);
+        assert_eq!(options.describe_attempt(1), "");
+        assert_eq!(options.describe_attempt(4), " (attempt 4/11)");
+    }
+}
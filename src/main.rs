@@ -1,5 +1,6 @@
-use suitcase::run;
+use std::process::ExitCode;
+use suitcase::{exit_code, run};
 
-fn main() -> anyhow::Result<()> {
-    run(None)
+fn main() -> ExitCode {
+    exit_code(run(None))
 }